@@ -0,0 +1,480 @@
+use ruwren::{create_module, Class, ModuleLibrary, SlotId, SlotType, VM};
+use std::{
+    cell::RefCell,
+    collections::{BTreeMap, BTreeSet},
+    fs, io,
+    path::{Component, Path, PathBuf},
+    sync::{Arc, Mutex},
+};
+
+use super::{
+    manifest::Permission,
+    registry::{namespaced, ContentKind, Registry},
+    settings::{SettingValue, Settings},
+    source::{Asset, ModuleSource},
+};
+
+// Bumped whenever the `engine` Wren module changes in a way scripts can see.
+pub const API_VERSION: &str = "0.5.0";
+
+pub const ENGINE_MODULE: &str = "engine";
+pub const ENGINE_MODULE_SOURCE: &str = include_str!("engine.wren");
+
+pub struct LoadingModule {
+    pub name: String,
+    pub source: ModuleSource,
+    pub settings: Settings,
+    pub permissions: Vec<Permission>,
+}
+
+#[derive(Default)]
+pub struct ApiState {
+    pub registry: Registry,
+
+    // The engine module whose entrypoint is currently running, if any.
+    pub loading: Option<LoadingModule>,
+    // Loaded engine modules and their entrypoint files.
+    pub modules: BTreeMap<String, PathBuf>,
+    // Contains a data directory for every module with the `filesystem`
+    // permission.
+    pub module_data_dir: PathBuf,
+    // What `Lang.translate` translates to.
+    pub locale: String,
+
+    // event -> module of each handler, in the order `Events` keeps them
+    pub handlers: BTreeMap<String, Vec<String>>,
+    // Modules that had a call run over its budget. Nothing of theirs is
+    // called anymore.
+    pub disabled: BTreeSet<String>,
+}
+
+thread_local! {
+    // Foreign methods are plain functions, so they reach the host's state
+    // through here, along with the module whose VM runs on this thread. Set
+    // on every module's worker thread by `ScriptHost::run_entrypoint`.
+    static STATE: RefCell<Option<(Arc<Mutex<ApiState>>, String)>> = const { RefCell::new(None) };
+}
+
+pub fn install(state: Arc<Mutex<ApiState>>, module: String) {
+    STATE.with(|s| *s.borrow_mut() = Some((state, module)));
+}
+
+fn with_state<T>(f: impl FnOnce(&mut ApiState, &str) -> T) -> T {
+    STATE.with(|s| {
+        let s = s.borrow();
+        let (state, module) = s
+            .as_ref()
+            .expect("scripting API used without a script host");
+        let mut state = state.lock().unwrap();
+        f(&mut state, module)
+    })
+}
+
+fn abort(vm: &VM, message: &str) {
+    vm.set_slot_string(0, message);
+    vm.abort_fiber(0);
+}
+
+// The string argument `name` in `slot`, or `None` with the fiber aborted if
+// the script passed something else.
+fn string_arg(vm: &VM, slot: SlotId, name: &str) -> Option<String> {
+    if vm.get_slot_type(slot) != SlotType::String {
+        abort(vm, &format!("`{name}` must be a string"));
+        return None;
+    }
+
+    vm.get_slot_string(slot)
+}
+
+// Aborts the fiber if a call of this module ran over its budget. The host
+// stops waiting for such a call, but it keeps running on its worker thread
+// until it returns or calls into the engine. Checked by every foreign method.
+fn out_of_time(vm: &VM) -> bool {
+    let over = with_state(|state, module| state.disabled.contains(module));
+    if over {
+        abort(vm, "script ran over its budget");
+    }
+
+    over
+}
+
+// Whether `path` is a relative path that stays inside the directory it is
+// relative to, so no `..`, root or prefix.
+fn stays_inside(path: &Path) -> bool {
+    path.components().next().is_some()
+        && path.components().all(|c| matches!(c, Component::Normal(_)))
+}
+
+fn denied(module: &str, permission: Permission) -> String {
+    format!(
+        "module {module} doesn't have the `{permission}` permission, \
+         add it to `permissions` in its module.yml"
+    )
+}
+
+pub fn library() -> ModuleLibrary {
+    let mut lib = ModuleLibrary::new();
+    engine::publish_module(&mut lib);
+    lib
+}
+
+pub struct EngineClass;
+
+impl Class for EngineClass {
+    fn initialize(_: &VM) -> Self {
+        Self
+    }
+}
+
+impl EngineClass {
+    fn api_version(vm: &VM) {
+        if out_of_time(vm) {
+            return;
+        }
+        vm.set_slot_string(0, API_VERSION);
+    }
+}
+
+pub struct RegistryClass;
+
+impl Class for RegistryClass {
+    fn initialize(_: &VM) -> Self {
+        Self
+    }
+}
+
+impl RegistryClass {
+    fn block(vm: &VM) {
+        Self::register(vm, ContentKind::Block);
+    }
+
+    fn item(vm: &VM) {
+        Self::register(vm, ContentKind::Item);
+    }
+
+    fn entity(vm: &VM) {
+        Self::register(vm, ContentKind::Entity);
+    }
+
+    fn register(vm: &VM, kind: ContentKind) {
+        if out_of_time(vm) {
+            return;
+        }
+
+        let Some(id) = string_arg(vm, 1, "id") else {
+            return;
+        };
+
+        let registered = with_state(|state, _| {
+            let module = state.loading.as_ref()?.name.clone();
+            let id = namespaced(&module, &id);
+            state.registry.register(kind, id, &module);
+            Some(())
+        });
+
+        if registered.is_none() {
+            abort(
+                vm,
+                &format!("a {kind} can only be registered while a module is loading"),
+            );
+        }
+    }
+
+    fn texture(vm: &VM) {
+        if out_of_time(vm) {
+            return;
+        }
+
+        let Some(id) = string_arg(vm, 1, "id") else {
+            return;
+        };
+        let Some(path) = string_arg(vm, 2, "path") else {
+            return;
+        };
+        if !stays_inside(Path::new(&path)) {
+            abort(vm, &format!("`{path}` is not a path inside the module"));
+            return;
+        }
+
+        let registered = with_state(|state, _| {
+            let loading = state.loading.as_ref()?;
+            let id = namespaced(&loading.name, &id);
+            let asset = Asset {
+                source: loading.source.clone(),
+                path: path.into(),
+            };
+            state.registry.register_texture(id, asset);
+            Some(())
+        });
+
+        if registered.is_none() {
+            abort(
+                vm,
+                "a texture can only be registered while a module is loading",
+            );
+        }
+    }
+
+    fn command(vm: &VM) {
+        if out_of_time(vm) {
+            return;
+        }
+
+        let Some(name) = string_arg(vm, 1, "name") else {
+            return;
+        };
+
+        let registered = with_state(|state, _| {
+            let module = state.loading.as_ref()?.name.clone();
+            state.registry.register_command(name, &module);
+            Some(())
+        });
+
+        if registered.is_none() {
+            abort(
+                vm,
+                "a command can only be registered while a module is loading",
+            );
+        }
+    }
+
+    fn handler(vm: &VM) {
+        if out_of_time(vm) {
+            return;
+        }
+
+        let Some(event) = string_arg(vm, 1, "event") else {
+            return;
+        };
+
+        let registered = with_state(|state, _| {
+            let module = state.loading.as_ref()?.name.clone();
+            state.handlers.entry(event).or_default().push(module);
+            Some(())
+        });
+
+        if registered.is_none() {
+            abort(
+                vm,
+                "an event handler can only be added while a module is loading",
+            );
+        }
+    }
+}
+
+pub struct SettingsClass;
+
+impl Class for SettingsClass {
+    fn initialize(_: &VM) -> Self {
+        Self
+    }
+}
+
+impl SettingsClass {
+    fn get(vm: &VM) {
+        if out_of_time(vm) {
+            return;
+        }
+
+        let Some(option) = string_arg(vm, 1, "option") else {
+            return;
+        };
+
+        let value = with_state(|state, _| {
+            let loading = state.loading.as_ref()?;
+            Some(loading.settings.get(&option).cloned())
+        });
+
+        match value {
+            Some(Some(SettingValue::String(s))) => vm.set_slot_string(0, &s),
+            Some(Some(SettingValue::Integer(i))) => vm.set_slot_double(0, i as f64),
+            Some(Some(SettingValue::Float(x))) => vm.set_slot_double(0, x),
+            Some(Some(SettingValue::Boolean(b))) => vm.set_slot_bool(0, b),
+            Some(None) => vm.set_slot_null(0),
+            None => abort(vm, "settings can only be read while a module is loading"),
+        }
+    }
+}
+
+pub struct LangClass;
+
+impl Class for LangClass {
+    fn initialize(_: &VM) -> Self {
+        Self
+    }
+}
+
+impl LangClass {
+    // `Lang.translate` turns the arguments into strings before passing them.
+    fn translate(vm: &VM) {
+        if out_of_time(vm) {
+            return;
+        }
+
+        let Some(key) = string_arg(vm, 1, "key") else {
+            return;
+        };
+        let Some(count) = vm.get_list_count(2) else {
+            abort(vm, "`args` must be a list");
+            return;
+        };
+
+        let mut args = Vec::with_capacity(count);
+        for idx in 0..count {
+            vm.get_list_element(2, idx as i32, 3);
+            let Some(arg) = string_arg(vm, 3, "args") else {
+                return;
+            };
+            args.push(arg);
+        }
+        let args = args.iter().map(String::as_str).collect::<Vec<_>>();
+
+        let text = with_state(|state, _| {
+            state
+                .registry
+                .translations()
+                .translate(&state.locale, &key, &args)
+        });
+        vm.set_slot_string(0, text);
+    }
+}
+
+// A module's handle on its own data directory, opened with `Files.open()`.
+// Handles can only be opened while a module is loading, so handlers that run
+// later still only reach the directory of the module that opened them.
+pub struct FilesClass {
+    // The module's data directory, or why the handle couldn't be opened.
+    dir: Result<PathBuf, String>,
+}
+
+impl Class for FilesClass {
+    fn initialize(vm: &VM) -> Self {
+        let dir = with_state(|state, _| {
+            let loading = state
+                .loading
+                .as_ref()
+                .ok_or("files can only be opened while a module is loading")?;
+            if !loading.permissions.contains(&Permission::Filesystem) {
+                return Err(denied(&loading.name, Permission::Filesystem));
+            }
+
+            Ok(state.module_data_dir.join(&loading.name))
+        });
+
+        if let Err(e) = &dir {
+            abort(vm, e);
+        }
+
+        Self { dir }
+    }
+}
+
+impl FilesClass {
+    fn read(&self, vm: &VM) {
+        if out_of_time(vm) {
+            return;
+        }
+
+        let Some(path) = string_arg(vm, 1, "path") else {
+            return;
+        };
+        let Some(path) = self.resolve(vm, &path) else {
+            return;
+        };
+
+        match fs::read_to_string(&path) {
+            Ok(contents) => vm.set_slot_string(0, &contents),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => vm.set_slot_null(0),
+            Err(e) => abort(vm, &format!("failed to read {}: {e}", path.display())),
+        }
+    }
+
+    fn write(&self, vm: &VM) {
+        if out_of_time(vm) {
+            return;
+        }
+
+        let Some(path) = string_arg(vm, 1, "path") else {
+            return;
+        };
+        let Some(contents) = string_arg(vm, 2, "contents") else {
+            return;
+        };
+        let Some(path) = self.resolve(vm, &path) else {
+            return;
+        };
+
+        let result =
+            fs::create_dir_all(path.parent().unwrap()).and_then(|()| fs::write(&path, contents));
+        if let Err(e) = result {
+            abort(vm, &format!("failed to write {}: {e}", path.display()));
+        }
+    }
+
+    fn exists(&self, vm: &VM) {
+        if out_of_time(vm) {
+            return;
+        }
+
+        let Some(path) = string_arg(vm, 1, "path") else {
+            return;
+        };
+        if let Some(path) = self.resolve(vm, &path) {
+            vm.set_slot_bool(0, path.is_file());
+        }
+    }
+
+    // Aborts the fiber if the handle isn't usable or `path` would leave the
+    // data directory.
+    fn resolve(&self, vm: &VM, path: &str) -> Option<PathBuf> {
+        let dir = match &self.dir {
+            Ok(dir) => dir,
+            Err(e) => {
+                abort(vm, e);
+                return None;
+            }
+        };
+
+        let relative = Path::new(path);
+        if !stays_inside(relative) {
+            abort(
+                vm,
+                &format!("`{path}` is not a path inside the module's data directory"),
+            );
+            return None;
+        }
+
+        Some(dir.join(relative))
+    }
+}
+
+create_module! {
+    class("Engine") crate::game::api::EngineClass => engine_class {
+        static(getter "apiVersion") api_version
+    }
+
+    class("Registry") crate::game::api::RegistryClass => registry_class {
+        static(fn "block", 1) block,
+        static(fn "item", 1) item,
+        static(fn "entity", 1) entity,
+        static(fn "texture", 2) texture,
+        static(fn "command_", 1) command,
+        static(fn "handler_", 1) handler
+    }
+
+    class("Settings") crate::game::api::SettingsClass => settings_class {
+        static(fn "get", 1) get
+    }
+
+    class("Lang") crate::game::api::LangClass => lang_class {
+        static(fn "translate_", 2) translate
+    }
+
+    class("Files") crate::game::api::FilesClass => files_class {
+        instance(fn "read", 1) read,
+        instance(fn "write", 2) write,
+        instance(fn "exists", 1) exists
+    }
+
+    module => engine
+}
@@ -0,0 +1,36 @@
+use std::{io, path::Path};
+
+use super::{
+    registry::namespaced,
+    source::{Asset, ModuleSource},
+};
+
+pub const TEXTURES_DIR: &str = "assets/textures";
+
+// Finds every `.png` under a module's `assets/textures`, keyed as
+// `module:path/without/extension`.
+pub fn find_textures(module: &str, source: &ModuleSource) -> io::Result<Vec<(String, Asset)>> {
+    let root = Path::new(TEXTURES_DIR);
+
+    Ok(source
+        .list(root)?
+        .into_iter()
+        .filter(|path| path.extension().is_some_and(|ext| ext == "png"))
+        .map(|path| {
+            let key = path
+                .strip_prefix(root)
+                .unwrap()
+                .with_extension("")
+                .components()
+                .map(|c| c.as_os_str().to_string_lossy())
+                .collect::<Vec<_>>()
+                .join("/");
+            let asset = Asset {
+                source: source.clone(),
+                path,
+            };
+
+            (namespaced(module, &key), asset)
+        })
+        .collect())
+}
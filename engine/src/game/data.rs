@@ -0,0 +1,340 @@
+use std::{
+    collections::BTreeMap,
+    fmt, io,
+    path::{Path, PathBuf},
+};
+use yaml_rust::{yaml::Hash, Yaml, YamlLoader};
+
+use super::{merge_yaml, registry::namespaced, source::ModuleSource};
+
+pub const DATA_DIR: &str = "data";
+pub const PATCHES_DIR: &str = "patches";
+
+const PATCH_OPS: &[&str] = &["add", "replace", "delete", "merge"];
+
+// table -> namespaced key -> entry, e.g. `blocks` -> `core:stone` -> `{ hardness: 1 }`
+pub type DataTables = BTreeMap<String, BTreeMap<String, Yaml>>;
+
+#[derive(Debug)]
+pub enum DataErrorKind {
+    Io(io::Error),
+    Syntax(yaml_rust::ScanError),
+    WrongType { expected: &'static str },
+    InvalidPatch(String),
+    Exists,
+    Missing,
+}
+
+#[derive(Debug)]
+pub struct DataError {
+    pub file: PathBuf,
+    // The namespaced key the error is about, if any.
+    pub key: Option<String>,
+    pub kind: DataErrorKind,
+}
+
+impl fmt::Display for DataError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.file.display())?;
+        if let Some(key) = &self.key {
+            write!(f, ": `{key}`")?;
+        }
+
+        match &self.kind {
+            DataErrorKind::Io(e) => write!(f, ": {e}"),
+            DataErrorKind::Syntax(e) => write!(f, ": {e}"),
+            DataErrorKind::WrongType { expected } => write!(f, ": expected {expected}"),
+            DataErrorKind::InvalidPatch(e) => write!(f, ": {e}"),
+            DataErrorKind::Exists => write!(
+                f,
+                " already exists, use a `replace` or `merge` patch to change it"
+            ),
+            DataErrorKind::Missing => write!(f, " doesn't exist"),
+        }
+    }
+}
+
+impl std::error::Error for DataError {}
+
+// Adds the entries of a module's `data/*.yml` files to `tables`, then applies
+// its `patches/*.yml`. Each file's path below `data` or `patches`, without the
+// extension, names the table it belongs to. Keys are namespaced like registry
+// ids, so `stone` in the `core` module is `core:stone`.
+pub fn load(module: &str, source: &ModuleSource, tables: &mut DataTables) -> Result<(), DataError> {
+    for (table, file) in find_files(source, DATA_DIR)? {
+        let error = |key: Option<String>, kind| DataError {
+            file: source.display_path(&file),
+            key,
+            kind,
+        };
+
+        let entries = match load_yaml(source, &file)? {
+            Yaml::Hash(hash) => hash,
+            Yaml::Null => Hash::new(),
+            _ => {
+                return Err(error(
+                    None,
+                    DataErrorKind::WrongType {
+                        expected: "a map of entries",
+                    },
+                ))
+            }
+        };
+
+        let table = tables.entry(table).or_default();
+        for (key, value) in entries {
+            let Yaml::String(key) = key else {
+                return Err(error(
+                    None,
+                    DataErrorKind::WrongType {
+                        expected: "string keys",
+                    },
+                ));
+            };
+
+            let key = namespaced(module, &key);
+            if table.contains_key(&key) {
+                return Err(error(Some(key), DataErrorKind::Exists));
+            }
+            table.insert(key, value);
+        }
+    }
+
+    for (table, file) in find_files(source, PATCHES_DIR)? {
+        let error = |key: Option<String>, kind| DataError {
+            file: source.display_path(&file),
+            key,
+            kind,
+        };
+
+        let patches = match load_yaml(source, &file)? {
+            Yaml::Array(patches) => patches,
+            Yaml::Null => vec![],
+            _ => {
+                return Err(error(
+                    None,
+                    DataErrorKind::WrongType {
+                        expected: "a list of patches",
+                    },
+                ))
+            }
+        };
+
+        let table = tables.entry(table).or_default();
+        for patch in patches {
+            apply_patch(module, table, patch).map_err(|(key, kind)| error(key, kind))?;
+        }
+    }
+
+    Ok(())
+}
+
+// A patch is a map with exactly one of `add`, `replace`, `delete` or `merge`
+// naming the key to change, and a `value` for everything but `delete`.
+fn apply_patch(
+    module: &str,
+    table: &mut BTreeMap<String, Yaml>,
+    patch: Yaml,
+) -> Result<(), (Option<String>, DataErrorKind)> {
+    let invalid = |message: String| (None, DataErrorKind::InvalidPatch(message));
+
+    let Yaml::Hash(mut patch) = patch else {
+        return Err(invalid("expected each patch to be a map".to_string()));
+    };
+
+    let mut ops = PATCH_OPS
+        .iter()
+        .filter_map(|op| Some((*op, patch.remove(&Yaml::String(op.to_string()))?)));
+    let (op, key) = match (ops.next(), ops.next()) {
+        (Some((op, Yaml::String(key))), None) => (op, namespaced(module, &key)),
+        (Some((op, _)), None) => return Err(invalid(format!("`{op}` must be a string key"))),
+        _ => {
+            return Err(invalid(
+                "each patch needs exactly one of `add`, `replace`, `delete` or `merge`".to_string(),
+            ))
+        }
+    };
+
+    let value = patch.remove(&Yaml::String("value".to_string()));
+    if let Some(unknown) = patch.keys().next() {
+        let unknown = match unknown {
+            Yaml::String(s) => format!("`{s}`"),
+            other => format!("{other:?}"),
+        };
+        return Err(invalid(format!("unknown key {unknown} in patch")));
+    }
+
+    let value = match (op, value) {
+        ("delete", None) => Yaml::Null,
+        ("delete", Some(_)) => return Err(invalid("`delete` takes no `value`".to_string())),
+        (_, Some(value)) => value,
+        (op, None) => return Err(invalid(format!("`{op}` needs a `value`"))),
+    };
+
+    let exists = table.contains_key(&key);
+    match op {
+        "add" if exists => return Err((Some(key), DataErrorKind::Exists)),
+        "add" => {
+            table.insert(key, value);
+        }
+        _ if !exists => return Err((Some(key), DataErrorKind::Missing)),
+        "replace" => {
+            table.insert(key, value);
+        }
+        "delete" => {
+            table.remove(&key);
+        }
+        _ => match (table.get_mut(&key), value) {
+            (Some(Yaml::Hash(base)), Yaml::Hash(overlay)) => merge_yaml(base, overlay),
+            _ => {
+                return Err((
+                    Some(key),
+                    DataErrorKind::WrongType {
+                        expected: "maps on both sides of a `merge`",
+                    },
+                ))
+            }
+        },
+    }
+
+    Ok(())
+}
+
+// Every `.yml` file below `dir`, along with the table it belongs to.
+fn find_files(source: &ModuleSource, dir: &str) -> Result<Vec<(String, PathBuf)>, DataError> {
+    let root = Path::new(dir);
+
+    let files = source.list(root).map_err(|e| DataError {
+        file: source.display_path(root),
+        key: None,
+        kind: DataErrorKind::Io(e),
+    })?;
+
+    Ok(files
+        .into_iter()
+        .filter(|path| path.extension().is_some_and(|ext| ext == "yml"))
+        .map(|path| {
+            let table = path
+                .strip_prefix(root)
+                .unwrap()
+                .with_extension("")
+                .components()
+                .map(|c| c.as_os_str().to_string_lossy())
+                .collect::<Vec<_>>()
+                .join("/");
+
+            (table, path)
+        })
+        .collect())
+}
+
+fn load_yaml(source: &ModuleSource, file: &Path) -> Result<Yaml, DataError> {
+    let error = |kind| DataError {
+        file: source.display_path(file),
+        key: None,
+        kind,
+    };
+
+    let yaml_str = source
+        .read_to_string(file)
+        .map_err(|e| error(DataErrorKind::Io(e)))?;
+
+    Ok(YamlLoader::load_from_str(&yaml_str)
+        .map_err(|e| error(DataErrorKind::Syntax(e)))?
+        .into_iter()
+        .next()
+        .unwrap_or(Yaml::Null))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn yaml(s: &str) -> Yaml {
+        YamlLoader::load_from_str(s).unwrap().remove(0)
+    }
+
+    fn table() -> BTreeMap<String, Yaml> {
+        BTreeMap::from([
+            (
+                "core:stone".to_string(),
+                yaml("hardness: 1\ndrops:\n  item: core:cobblestone\n  count: 1\n"),
+            ),
+            ("core:sand".to_string(), yaml("hardness: 0")),
+        ])
+    }
+
+    fn patch(table: &mut BTreeMap<String, Yaml>, patch: &str) -> Result<(), DataErrorKind> {
+        apply_patch("hard_mode", table, yaml(patch)).map_err(|(_, kind)| kind)
+    }
+
+    #[test]
+    fn patch_ops() {
+        let mut table = table();
+
+        patch(
+            &mut table,
+            "merge: core:stone\nvalue:\n  hardness: 3\n  drops:\n    count: 2",
+        )
+        .unwrap();
+        let stone = &table["core:stone"];
+        assert_eq!(stone["hardness"], Yaml::Integer(3));
+        assert_eq!(stone["drops"]["item"].as_str(), Some("core:cobblestone"));
+        assert_eq!(stone["drops"]["count"], Yaml::Integer(2));
+
+        patch(&mut table, "replace: core:sand\nvalue:\n  hardness: 2").unwrap();
+        assert_eq!(table["core:sand"], yaml("hardness: 2"));
+
+        patch(&mut table, "delete: core:sand").unwrap();
+        assert!(!table.contains_key("core:sand"));
+
+        // Keys without a namespace belong to the patching module.
+        patch(&mut table, "add: obsidian\nvalue:\n  hardness: 10").unwrap();
+        assert_eq!(table["hard_mode:obsidian"], yaml("hardness: 10"));
+    }
+
+    #[test]
+    fn patches_of_missing_or_existing_entries() {
+        let mut table = table();
+
+        let err = apply_patch("core", &mut table, yaml("add: stone\nvalue: 1")).unwrap_err();
+        assert_eq!(err.0.as_deref(), Some("core:stone"));
+        assert!(matches!(err.1, DataErrorKind::Exists));
+
+        for op in ["replace", "merge"] {
+            let err = patch(&mut table, &format!("{op}: core:dirt\nvalue: {{}}")).unwrap_err();
+            assert!(matches!(err, DataErrorKind::Missing), "{op}");
+        }
+        let err = patch(&mut table, "delete: core:dirt").unwrap_err();
+        assert!(matches!(err, DataErrorKind::Missing));
+
+        let err = patch(&mut table, "merge: core:stone\nvalue: 3").unwrap_err();
+        assert!(matches!(err, DataErrorKind::WrongType { .. }));
+
+        assert_eq!(table, self::table());
+    }
+
+    #[test]
+    fn invalid_patches() {
+        let cases = [
+            ("- add: stone", "expected each patch to be a map"),
+            ("value: 1", "exactly one of"),
+            ("add: a\nreplace: b\nvalue: 1", "exactly one of"),
+            ("add: [a]\nvalue: 1", "`add` must be a string key"),
+            ("add: a\nvalue: 1\nwith: 2", "unknown key `with` in patch"),
+            ("delete: core:sand\nvalue: 1", "`delete` takes no `value`"),
+            ("replace: core:sand", "`replace` needs a `value`"),
+        ];
+
+        for (source, expected) in cases {
+            let mut table = table();
+            match patch(&mut table, source) {
+                Err(DataErrorKind::InvalidPatch(message)) => {
+                    assert!(message.contains(expected), "{source}: {message}")
+                }
+                other => panic!("{source}: {other:?}"),
+            }
+            assert_eq!(table, self::table(), "{source}");
+        }
+    }
+}
@@ -0,0 +1,253 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt, io,
+    path::{Path, PathBuf},
+};
+use yaml_rust::{Yaml, YamlLoader};
+
+use super::source::ModuleSource;
+
+pub const LANG_DIR: &str = "lang";
+
+#[derive(Debug)]
+pub enum LangErrorKind {
+    Io(io::Error),
+    Syntax(yaml_rust::ScanError),
+    WrongType { expected: &'static str },
+}
+
+#[derive(Debug)]
+pub struct LangError {
+    pub file: PathBuf,
+    pub key: Option<String>,
+    pub kind: LangErrorKind,
+}
+
+impl fmt::Display for LangError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.file.display())?;
+        if let Some(key) = &self.key {
+            write!(f, ": `{key}`")?;
+        }
+
+        match &self.kind {
+            LangErrorKind::Io(e) => write!(f, ": {e}"),
+            LangErrorKind::Syntax(e) => write!(f, ": {e}"),
+            LangErrorKind::WrongType { expected } => write!(f, ": expected {expected}"),
+        }
+    }
+}
+
+impl std::error::Error for LangError {}
+
+// The string tables of every loaded module, merged in load order so later
+// modules can replace the text of earlier ones.
+#[derive(Default, Debug)]
+pub struct Translations {
+    // locale -> key -> text
+    tables: BTreeMap<String, BTreeMap<String, String>>,
+}
+
+impl Translations {
+    // Loads every `lang/<locale>.yml` of a module. Nested maps are flattened
+    // into dotted keys, so `menu: { play: Play }` defines `menu.play`.
+    pub fn load(&mut self, source: &ModuleSource) -> Result<(), LangError> {
+        let root = Path::new(LANG_DIR);
+        let files = source.list(root).map_err(|e| LangError {
+            file: source.display_path(root),
+            key: None,
+            kind: LangErrorKind::Io(e),
+        })?;
+
+        // Only the files directly in `lang`, named after their locale.
+        let files = files.into_iter().filter(|path| {
+            path.parent() == Some(root) && path.extension().is_some_and(|ext| ext == "yml")
+        });
+        for file in files {
+            let locale = file.file_stem().unwrap().to_string_lossy().into_owned();
+
+            let error = |key: Option<String>, kind| LangError {
+                file: source.display_path(&file),
+                key,
+                kind,
+            };
+
+            let yaml_str = source
+                .read_to_string(&file)
+                .map_err(|e| error(None, LangErrorKind::Io(e)))?;
+            let yaml = YamlLoader::load_from_str(&yaml_str)
+                .map_err(|e| error(None, LangErrorKind::Syntax(e)))?
+                .into_iter()
+                .next()
+                .unwrap_or(Yaml::Null);
+
+            let table = self.tables.entry(locale).or_default();
+            match &yaml {
+                Yaml::Hash(_) => flatten(&yaml, "", table).map_err(|(key, expected)| {
+                    error(Some(key), LangErrorKind::WrongType { expected })
+                })?,
+                Yaml::Null => {}
+                _ => {
+                    return Err(error(
+                        None,
+                        LangErrorKind::WrongType {
+                            expected: "a map of keys to text",
+                        },
+                    ))
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    // Looks `key` up in `locale`, then in each less specific locale, e.g.
+    // `en-GB` and then `en`.
+    pub fn lookup(&self, locale: &str, key: &str) -> Option<&str> {
+        fallbacks(locale).find_map(|locale| Some(self.tables.get(locale)?.get(key)?.as_str()))
+    }
+
+    // Falls back to the key itself if no locale has it. `{0}`, `{1}` and so
+    // on in the text are replaced with `args`.
+    pub fn translate(&self, locale: &str, key: &str, args: &[&str]) -> String {
+        substitute(self.lookup(locale, key).unwrap_or(key), args)
+    }
+
+    // For every locale with a string table, the keys some other locale has
+    // that it would show untranslated.
+    pub fn missing(&self) -> BTreeMap<&str, Vec<&str>> {
+        let keys = self
+            .tables
+            .values()
+            .flat_map(|table| table.keys().map(String::as_str))
+            .collect::<BTreeSet<_>>();
+
+        self.tables
+            .keys()
+            .map(|locale| {
+                let missing = keys
+                    .iter()
+                    .copied()
+                    .filter(|key| self.lookup(locale, key).is_none())
+                    .collect();
+                (locale.as_str(), missing)
+            })
+            .collect()
+    }
+}
+
+// `zh-Hant-TW`, `zh-Hant`, `zh`
+fn fallbacks(locale: &str) -> impl Iterator<Item = &str> {
+    std::iter::successors(Some(locale), |locale| {
+        locale.rsplit_once('-').map(|(parent, _)| parent)
+    })
+}
+
+fn flatten(
+    yaml: &Yaml,
+    prefix: &str,
+    table: &mut BTreeMap<String, String>,
+) -> Result<(), (String, &'static str)> {
+    let Yaml::Hash(hash) = yaml else {
+        unreachable!()
+    };
+
+    for (key, value) in hash {
+        let key = match key {
+            Yaml::String(key) if prefix.is_empty() => key.clone(),
+            Yaml::String(key) => format!("{prefix}.{key}"),
+            _ => return Err((prefix.to_string(), "string keys")),
+        };
+
+        match value {
+            Yaml::String(text) => {
+                table.insert(key, text.clone());
+            }
+            Yaml::Hash(_) => flatten(value, &key, table)?,
+            _ => return Err((key, "text or a map of keys to text")),
+        }
+    }
+
+    Ok(())
+}
+
+// Replaces `{i}` with `args[i]` in a single pass from left to right, so
+// placeholders inside the arguments are left alone. `i` must be ASCII digits,
+// so `{+1}` or `{1.0}` aren't placeholders. Placeholders without an argument
+// are kept as they are.
+fn substitute(text: &str, args: &[&str]) -> String {
+    let mut result = String::with_capacity(text.len());
+    let mut rest = text;
+
+    while let Some(open) = rest.find('{') {
+        result.push_str(&rest[..open]);
+        rest = &rest[open..];
+
+        let arg = rest.find('}').and_then(|close| {
+            let idx = &rest[1..close];
+            if idx.is_empty() || !idx.bytes().all(|b| b.is_ascii_digit()) {
+                return None;
+            }
+            Some((close, args.get(idx.parse::<usize>().ok()?)?))
+        });
+        match arg {
+            Some((close, arg)) => {
+                result.push_str(arg);
+                rest = &rest[close + 1..];
+            }
+            None => {
+                result.push('{');
+                rest = &rest[1..];
+            }
+        }
+    }
+    result.push_str(rest);
+
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn substitutes_in_one_pass() {
+        assert_eq!(
+            substitute("{1}, {0}!", &["world", "Hello"]),
+            "Hello, world!"
+        );
+        assert_eq!(substitute("{0} and {1}", &["{1}", "x"]), "{1} and x");
+        assert_eq!(substitute("{0}{0}", &["a"]), "aa");
+        assert_eq!(substitute("{2} {x} {} {0", &["a"]), "{2} {x} {} {0");
+        assert_eq!(
+            substitute("{+1} {1.0} { 1} {-0}", &["a", "b"]),
+            "{+1} {1.0} { 1} {-0}"
+        );
+    }
+
+    #[test]
+    fn falls_back_to_less_specific_locales() {
+        let mut translations = Translations::default();
+        translations.tables.insert(
+            "en".to_string(),
+            BTreeMap::from([
+                ("greeting".to_string(), "Hello, {0}!".to_string()),
+                ("bye".to_string(), "Bye".to_string()),
+            ]),
+        );
+        translations.tables.insert(
+            "en-GB".to_string(),
+            BTreeMap::from([("bye".to_string(), "Cheerio".to_string())]),
+        );
+
+        assert_eq!(translations.translate("en-GB", "bye", &[]), "Cheerio");
+        assert_eq!(
+            translations.translate("en-GB", "greeting", &["Steve"]),
+            "Hello, Steve!"
+        );
+        assert_eq!(
+            translations.translate("de", "missing.key", &[]),
+            "missing.key"
+        );
+    }
+}
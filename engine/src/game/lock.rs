@@ -0,0 +1,178 @@
+use semver::Version;
+use sha2::{Digest, Sha256};
+use std::{
+    fmt, fs, io,
+    path::{Path, PathBuf},
+};
+use yaml_rust::{Yaml, YamlLoader};
+
+use super::source::ModuleSource;
+
+pub const LOCK_FILE: &str = "profile.lock";
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LockedModule {
+    pub name: String,
+    pub version: Version,
+    pub hash: String,
+}
+
+// The exact module set a profile resolved to, written next to `profile.yml`
+// so every machine launching the profile loads the same modules.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct Lockfile {
+    pub modules: Vec<LockedModule>,
+}
+
+#[derive(Debug)]
+pub enum LockError {
+    Io(PathBuf, io::Error),
+    Syntax(PathBuf, yaml_rust::ScanError),
+    Invalid(PathBuf, String),
+}
+
+impl fmt::Display for LockError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(path, e) => write!(f, "{}: {e}", path.display()),
+            Self::Syntax(path, e) => write!(f, "{}: {e}", path.display()),
+            Self::Invalid(path, message) => write!(f, "{}: {message}", path.display()),
+        }
+    }
+}
+
+impl std::error::Error for LockError {}
+
+impl Lockfile {
+    // Returns `None` if the profile hasn't been locked yet.
+    pub fn load(path: &Path) -> Result<Option<Self>, LockError> {
+        let yaml_str = match fs::read_to_string(path) {
+            Ok(s) => s,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(LockError::Io(path.to_path_buf(), e)),
+        };
+        let invalid = |message: &str| LockError::Invalid(path.to_path_buf(), message.to_string());
+
+        let yaml = YamlLoader::load_from_str(&yaml_str)
+            .map_err(|e| LockError::Syntax(path.to_path_buf(), e))?;
+        let Some(Yaml::Array(modules)) = yaml.first().map(|yaml| &yaml["modules"]) else {
+            return Err(invalid("expected a `modules` list"));
+        };
+
+        let modules = modules
+            .iter()
+            .map(|module| {
+                let (Some(name), Some(version), Some(hash)) = (
+                    module["name"].as_str(),
+                    module["version"].as_str(),
+                    module["hash"].as_str(),
+                ) else {
+                    return Err(invalid("every module needs a `name`, `version` and `hash`"));
+                };
+
+                Ok(LockedModule {
+                    name: name.to_string(),
+                    version: Version::parse(version)
+                        .map_err(|e| invalid(&format!("invalid version `{version}`: {e}")))?,
+                    hash: hash.to_string(),
+                })
+            })
+            .collect::<Result<_, _>>()?;
+
+        Ok(Some(Self { modules }))
+    }
+
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        let mut out = String::from("# Generated by the engine when the profile is resolved.\n");
+        if self.modules.is_empty() {
+            out.push_str("modules: []\n");
+        } else {
+            out.push_str("modules:\n");
+        }
+        for module in &self.modules {
+            out.push_str(&format!(
+                "  - name: {}\n    version: {}\n    hash: {}\n",
+                module.name, module.version, module.hash
+            ));
+        }
+
+        fs::write(path, out)
+    }
+
+    pub fn get(&self, name: &str) -> Option<&LockedModule> {
+        self.modules.iter().find(|module| module.name == name)
+    }
+}
+
+// Hashes every file in the module along with its path, so a module hashes the
+// same whether it's a directory or an archive.
+pub fn hash_module(source: &ModuleSource) -> io::Result<String> {
+    let mut hasher = Sha256::new();
+
+    for file in source.list(Path::new(""))? {
+        let bytes = source.read(&file)?;
+        let name = file
+            .components()
+            .map(|c| c.as_os_str().to_string_lossy())
+            .collect::<Vec<_>>()
+            .join("/");
+
+        hasher.update((name.len() as u64).to_le_bytes());
+        hasher.update(name.as_bytes());
+        hasher.update((bytes.len() as u64).to_le_bytes());
+        hasher.update(&bytes);
+    }
+
+    let hash = hasher
+        .finalize()
+        .iter()
+        .map(|b| format!("{b:02x}"))
+        .collect::<String>();
+
+    Ok(format!("sha256:{hash}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::testing::TempDir;
+
+    fn round_trip(lockfile: &Lockfile) -> Lockfile {
+        let dir = TempDir::new();
+        let path = dir.path().join(LOCK_FILE);
+        lockfile.save(&path).unwrap();
+
+        Lockfile::load(&path).unwrap().unwrap()
+    }
+
+    #[test]
+    fn saved_lockfiles_load_again() {
+        let lockfile = Lockfile {
+            modules: vec![
+                LockedModule {
+                    name: "core".to_string(),
+                    version: Version::new(1, 2, 3),
+                    hash: "sha256:00ff".to_string(),
+                },
+                LockedModule {
+                    name: "hard_mode".to_string(),
+                    version: Version::parse("0.1.0-beta.1").unwrap(),
+                    hash: "sha256:ff00".to_string(),
+                },
+            ],
+        };
+
+        assert_eq!(round_trip(&lockfile), lockfile);
+    }
+
+    #[test]
+    fn empty_lockfiles_load_again() {
+        assert_eq!(round_trip(&Lockfile::default()), Lockfile::default());
+    }
+
+    #[test]
+    fn missing_lockfiles_load_as_none() {
+        let dir = TempDir::new();
+        assert_eq!(Lockfile::load(&dir.path().join(LOCK_FILE)).unwrap(), None);
+    }
+}
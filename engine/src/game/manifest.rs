@@ -0,0 +1,524 @@
+use semver::{Version, VersionReq};
+use std::{
+    fmt, io,
+    path::{Component, Path, PathBuf},
+};
+use yaml_rust::{Yaml, YamlLoader};
+
+use super::{
+    api::ENGINE_MODULE,
+    is_valid_name,
+    settings::{self, SettingsSchema},
+    source::ModuleSource,
+    ModuleReq, ModuleReqError,
+};
+
+const KEYS: &[&str] = &[
+    "name",
+    "version",
+    "dependencies",
+    "optional_dependencies",
+    "conflicts",
+    "provides",
+    "authors",
+    "description",
+    "entrypoint",
+    "engine",
+    "settings",
+    "permissions",
+];
+
+// A virtual module name, like `worldgen`, that a module can stand in for.
+#[derive(Clone, Debug)]
+pub struct Capability {
+    pub name: String,
+    pub version: Version,
+}
+
+// Something a module's scripts can only do if the module asks for it under
+// `permissions`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Permission {
+    // Reading and writing files in the module's own data directory.
+    Filesystem,
+    // Reserved for future APIs. Accepted so manifests can already declare
+    // them, but nothing checks them yet.
+    Network,
+    Threads,
+}
+
+impl Permission {
+    fn parse(s: &str) -> Option<Self> {
+        match s {
+            "filesystem" => Some(Self::Filesystem),
+            "network" => Some(Self::Network),
+            "threads" => Some(Self::Threads),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Permission {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Filesystem => write!(f, "filesystem"),
+            Self::Network => write!(f, "network"),
+            Self::Threads => write!(f, "threads"),
+        }
+    }
+}
+
+// The parsed contents of a module's `module.yml`. See
+// `docs/module_manifest.md` for the schema.
+#[derive(Clone, Debug)]
+pub struct Manifest {
+    pub name: String,
+    pub version: Version,
+
+    pub dependencies: Vec<ModuleReq>,
+    pub optional_dependencies: Vec<ModuleReq>,
+    pub conflicts: Vec<ModuleReq>,
+    pub provides: Vec<Capability>,
+
+    pub authors: Vec<String>,
+    pub description: Option<String>,
+
+    pub entrypoint: PathBuf,
+    pub engine: VersionReq,
+
+    // `None` if the module doesn't declare its settings, in which case any
+    // option is accepted.
+    pub settings: Option<SettingsSchema>,
+    pub permissions: Vec<Permission>,
+}
+
+#[derive(Debug)]
+pub enum ManifestErrorKind {
+    Io(io::Error),
+    Syntax(yaml_rust::ScanError),
+    Empty,
+    NotAMap,
+    UnknownKey,
+    Missing,
+    InvalidName,
+    // Names the engine uses for itself, like the `engine` Wren module.
+    ReservedName,
+    WrongType { expected: &'static str },
+    InvalidVersion(semver::Error),
+    InvalidModuleReq(ModuleReqError),
+    InvalidCapability { capability: String },
+    InvalidSettings(String),
+    UnknownPermission { permission: String },
+    // An entrypoint that is absolute or leaves the module with `..`.
+    InvalidEntrypoint { entrypoint: String },
+}
+
+#[derive(Debug)]
+pub struct ManifestError {
+    pub path: PathBuf,
+    pub key: Option<String>,
+    pub kind: ManifestErrorKind,
+}
+
+impl fmt::Display for ManifestError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.path.display())?;
+        if let Some(key) = &self.key {
+            write!(f, ": `{key}`")?;
+        }
+
+        match &self.kind {
+            ManifestErrorKind::Io(e) => write!(f, ": {e}"),
+            ManifestErrorKind::Syntax(e) => write!(f, ": {e}"),
+            ManifestErrorKind::Empty => write!(f, ": file is empty"),
+            ManifestErrorKind::NotAMap => write!(f, ": expected a map at the top level"),
+            ManifestErrorKind::UnknownKey => write!(f, " is not a known key"),
+            ManifestErrorKind::Missing => write!(f, " is required"),
+            ManifestErrorKind::InvalidName => write!(
+                f,
+                ": invalid module name (expected letters, digits, `-` and `_` only)"
+            ),
+            ManifestErrorKind::ReservedName => {
+                write!(f, ": `{ENGINE_MODULE}` is reserved for the engine")
+            }
+            ManifestErrorKind::WrongType { expected } => write!(f, ": expected {expected}"),
+            ManifestErrorKind::InvalidVersion(e) => write!(f, ": {e}"),
+            ManifestErrorKind::InvalidModuleReq(e) => write!(f, ": {e}"),
+            ManifestErrorKind::InvalidCapability { capability } => write!(
+                f,
+                ": invalid capability `{capability}` (expected `name` or `name@version`)"
+            ),
+            ManifestErrorKind::InvalidSettings(e) => write!(f, ": {e}"),
+            ManifestErrorKind::UnknownPermission { permission } => write!(
+                f,
+                ": unknown permission `{permission}` (expected filesystem, network or threads)"
+            ),
+            ManifestErrorKind::InvalidEntrypoint { entrypoint } => {
+                write!(f, ": `{entrypoint}` is not a path inside the module")
+            }
+        }
+    }
+}
+
+impl std::error::Error for ManifestError {}
+
+impl Manifest {
+    pub fn load(source: &ModuleSource) -> Result<Self, ManifestError> {
+        let path = source.display_path("module.yml".as_ref());
+        let error = |key: Option<&str>, kind| ManifestError {
+            path: path.clone(),
+            key: key.map(str::to_string),
+            kind,
+        };
+
+        let yaml_str = source
+            .read_to_string("module.yml".as_ref())
+            .map_err(|e| error(None, ManifestErrorKind::Io(e)))?;
+        let yaml = YamlLoader::load_from_str(&yaml_str)
+            .map_err(|e| error(None, ManifestErrorKind::Syntax(e)))?
+            .into_iter()
+            .next()
+            .ok_or_else(|| error(None, ManifestErrorKind::Empty))?;
+
+        let Yaml::Hash(ref hash) = yaml else {
+            return Err(error(None, ManifestErrorKind::NotAMap));
+        };
+        for key in hash.keys() {
+            match key {
+                Yaml::String(s) if KEYS.contains(&s.as_str()) => {}
+                Yaml::String(s) => return Err(error(Some(s), ManifestErrorKind::UnknownKey)),
+                _ => {
+                    return Err(error(
+                        None,
+                        ManifestErrorKind::WrongType {
+                            expected: "string keys",
+                        },
+                    ))
+                }
+            }
+        }
+
+        let string = |key: &str| match &yaml[key] {
+            Yaml::String(s) => Ok(Some(s.clone())),
+            Yaml::BadValue => Ok(None),
+            _ => Err(error(
+                Some(key),
+                ManifestErrorKind::WrongType {
+                    expected: "a string",
+                },
+            )),
+        };
+        let string_list = |key: &str| match &yaml[key] {
+            Yaml::Array(arr) => arr
+                .iter()
+                .map(|item| match item {
+                    Yaml::String(s) => Ok(s.clone()),
+                    _ => Err(error(
+                        Some(key),
+                        ManifestErrorKind::WrongType {
+                            expected: "a list of strings",
+                        },
+                    )),
+                })
+                .collect(),
+            Yaml::BadValue => Ok(vec![]),
+            _ => Err(error(
+                Some(key),
+                ManifestErrorKind::WrongType {
+                    expected: "a list of strings",
+                },
+            )),
+        };
+        let module_reqs = |key: &str| {
+            string_list(key)?
+                .into_iter()
+                .map(|s| {
+                    ModuleReq::parse(s)
+                        .map_err(|e| error(Some(key), ManifestErrorKind::InvalidModuleReq(e)))
+                })
+                .collect::<Result<Vec<_>, _>>()
+        };
+
+        let name =
+            string("name")?.ok_or_else(|| error(Some("name"), ManifestErrorKind::Missing))?;
+        if !is_valid_name(&name) {
+            return Err(error(Some("name"), ManifestErrorKind::InvalidName));
+        }
+        if name == ENGINE_MODULE {
+            return Err(error(Some("name"), ManifestErrorKind::ReservedName));
+        }
+        let version = string("version")?
+            .ok_or_else(|| error(Some("version"), ManifestErrorKind::Missing))
+            .and_then(|s| {
+                Version::parse(&s)
+                    .map_err(|e| error(Some("version"), ManifestErrorKind::InvalidVersion(e)))
+            })?;
+        let engine = match string("engine")? {
+            Some(s) => VersionReq::parse(&s)
+                .map_err(|e| error(Some("engine"), ManifestErrorKind::InvalidVersion(e)))?,
+            None => VersionReq::STAR,
+        };
+
+        let provides = string_list("provides")?
+            .into_iter()
+            .map(|s| {
+                let (name, capability_version) = match s.split_once('@') {
+                    Some((name, v)) => (name.trim(), Version::parse(v.trim()).ok()),
+                    None => (s.trim(), Some(version.clone())),
+                };
+
+                match capability_version {
+                    Some(version) if is_valid_name(name) => Ok(Capability {
+                        name: name.to_string(),
+                        version,
+                    }),
+                    _ => Err(error(
+                        Some("provides"),
+                        ManifestErrorKind::InvalidCapability { capability: s },
+                    )),
+                }
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let settings = match &yaml["settings"] {
+            Yaml::BadValue => None,
+            schema => Some(
+                settings::parse_schema(schema)
+                    .map_err(|e| error(Some("settings"), ManifestErrorKind::InvalidSettings(e)))?,
+            ),
+        };
+
+        let permissions = string_list("permissions")?
+            .into_iter()
+            .map(|s| {
+                Permission::parse(&s).ok_or_else(|| {
+                    error(
+                        Some("permissions"),
+                        ManifestErrorKind::UnknownPermission { permission: s },
+                    )
+                })
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let entrypoint = string("entrypoint")?.unwrap_or_else(|| "main.wren".to_string());
+        let inside = !entrypoint.is_empty()
+            && Path::new(&entrypoint)
+                .components()
+                .all(|c| matches!(c, Component::Normal(_)));
+        if !inside {
+            return Err(error(
+                Some("entrypoint"),
+                ManifestErrorKind::InvalidEntrypoint { entrypoint },
+            ));
+        }
+
+        Ok(Self {
+            name,
+            version,
+            dependencies: module_reqs("dependencies")?,
+            optional_dependencies: module_reqs("optional_dependencies")?,
+            conflicts: module_reqs("conflicts")?,
+            provides,
+            authors: string_list("authors")?,
+            description: string("description")?,
+            entrypoint: entrypoint.into(),
+            engine,
+            settings,
+            permissions,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::testing::TempDir;
+
+    fn load(yaml: &str) -> Result<Manifest, ManifestError> {
+        let dir = TempDir::new();
+        dir.write("core/module.yml", yaml);
+        Manifest::load(&ModuleSource::Dir(dir.path().join("core")))
+    }
+
+    #[test]
+    fn full_manifests() {
+        let manifest = load(
+            "name: hard_mode\n\
+             version: 1.2.0\n\
+             dependencies: [core@^0.1]\n\
+             optional_dependencies: [ui]\n\
+             conflicts: [easy_mode]\n\
+             provides: [difficulty, worldgen@2.0.0]\n\
+             authors: [Alex, Sam]\n\
+             description: Mobs hit harder\n\
+             entrypoint: scripts/main.wren\n\
+             engine: ^0.5\n\
+             settings:\n  damage:\n    type: float\n\
+             permissions: [filesystem, network]\n",
+        )
+        .unwrap();
+
+        assert_eq!(manifest.name, "hard_mode");
+        assert_eq!(manifest.version, Version::new(1, 2, 0));
+        assert_eq!(manifest.dependencies[0].name(), "core");
+        assert_eq!(manifest.optional_dependencies[0].name(), "ui");
+        assert_eq!(manifest.conflicts[0].name(), "easy_mode");
+        let provides = manifest
+            .provides
+            .iter()
+            .map(|capability| format!("{}@{}", capability.name, capability.version))
+            .collect::<Vec<_>>();
+        assert_eq!(provides, ["difficulty@1.2.0", "worldgen@2.0.0"]);
+        assert_eq!(manifest.authors, ["Alex", "Sam"]);
+        assert_eq!(manifest.description.as_deref(), Some("Mobs hit harder"));
+        assert_eq!(manifest.engine.to_string(), "^0.5");
+        assert!(manifest.settings.unwrap().contains_key("damage"));
+        assert_eq!(
+            manifest.permissions,
+            [Permission::Filesystem, Permission::Network]
+        );
+
+        let manifest = load("name: core\nversion: 0.1.0\n").unwrap();
+        assert_eq!(manifest.engine, VersionReq::STAR);
+        assert!(manifest.settings.is_none());
+        assert!(manifest.dependencies.is_empty() && manifest.permissions.is_empty());
+    }
+
+    // A manifest, the key its error is about and whether the error is the
+    // right kind.
+    type ErrorCase = (
+        &'static str,
+        Option<&'static str>,
+        fn(&ManifestErrorKind) -> bool,
+    );
+
+    #[test]
+    fn manifest_errors() {
+        let cases: &[ErrorCase] = &[
+            ("name: [", None, |kind| {
+                matches!(kind, ManifestErrorKind::Syntax(_))
+            }),
+            ("", None, |kind| matches!(kind, ManifestErrorKind::Empty)),
+            ("- core", None, |kind| {
+                matches!(kind, ManifestErrorKind::NotAMap)
+            }),
+            (
+                "name: core\nversion: 0.1.0\nlicense: MIT",
+                Some("license"),
+                |kind| matches!(kind, ManifestErrorKind::UnknownKey),
+            ),
+            ("version: 0.1.0", Some("name"), |kind| {
+                matches!(kind, ManifestErrorKind::Missing)
+            }),
+            ("name: core", Some("version"), |kind| {
+                matches!(kind, ManifestErrorKind::Missing)
+            }),
+            ("name: co re\nversion: 0.1.0", Some("name"), |kind| {
+                matches!(kind, ManifestErrorKind::InvalidName)
+            }),
+            ("name: engine\nversion: 0.1.0", Some("name"), |kind| {
+                matches!(kind, ManifestErrorKind::ReservedName)
+            }),
+            ("name: [core]\nversion: 0.1.0", Some("name"), |kind| {
+                matches!(
+                    kind,
+                    ManifestErrorKind::WrongType {
+                        expected: "a string"
+                    }
+                )
+            }),
+            (
+                "name: core\nversion: 0.1.0\nauthors: [1]",
+                Some("authors"),
+                |kind| {
+                    matches!(
+                        kind,
+                        ManifestErrorKind::WrongType {
+                            expected: "a list of strings"
+                        }
+                    )
+                },
+            ),
+            ("name: core\nversion: 0.1.0\n1: a", None, |kind| {
+                matches!(
+                    kind,
+                    ManifestErrorKind::WrongType {
+                        expected: "string keys"
+                    }
+                )
+            }),
+            ("name: core\nversion: one", Some("version"), |kind| {
+                matches!(kind, ManifestErrorKind::InvalidVersion(_))
+            }),
+            (
+                "name: core\nversion: 0.1.0\nengine: newest",
+                Some("engine"),
+                |kind| matches!(kind, ManifestErrorKind::InvalidVersion(_)),
+            ),
+            (
+                "name: core\nversion: 0.1.0\ndependencies: [ui@newest]",
+                Some("dependencies"),
+                |kind| matches!(kind, ManifestErrorKind::InvalidModuleReq(_)),
+            ),
+            (
+                "name: core\nversion: 0.1.0\nprovides: [world@gen]",
+                Some("provides"),
+                |kind| matches!(kind, ManifestErrorKind::InvalidCapability { capability } if capability == "world@gen"),
+            ),
+            (
+                "name: core\nversion: 0.1.0\nsettings: [seed]",
+                Some("settings"),
+                |kind| matches!(kind, ManifestErrorKind::InvalidSettings(_)),
+            ),
+            (
+                "name: core\nversion: 0.1.0\npermissions: [root]",
+                Some("permissions"),
+                |kind| matches!(kind, ManifestErrorKind::UnknownPermission { permission } if permission == "root"),
+            ),
+            (
+                "name: core\nversion: 0.1.0\nentrypoint: ../main.wren",
+                Some("entrypoint"),
+                |kind| matches!(kind, ManifestErrorKind::InvalidEntrypoint { .. }),
+            ),
+        ];
+
+        for (source, key, is_kind) in cases {
+            let err = load(source).unwrap_err();
+            assert!(is_kind(&err.kind), "{source:?}: {err}");
+            assert_eq!(err.key.as_deref(), *key, "{source:?}");
+            assert!(err.path.ends_with("core/module.yml"));
+        }
+
+        let dir = TempDir::new();
+        let err = Manifest::load(&ModuleSource::Dir(dir.path().to_path_buf())).unwrap_err();
+        assert!(
+            matches!(&err.kind, ManifestErrorKind::Io(e) if e.kind() == io::ErrorKind::NotFound),
+            "{err}"
+        );
+    }
+
+    #[test]
+    fn entrypoints_stay_inside_the_module() {
+        let manifest = load("name: core\nversion: 0.1.0\n").unwrap();
+        assert_eq!(manifest.entrypoint, Path::new("main.wren"));
+        let manifest = load("name: core\nversion: 0.1.0\nentrypoint: scripts/init.wren\n").unwrap();
+        assert_eq!(manifest.entrypoint, Path::new("scripts/init.wren"));
+
+        for entrypoint in [
+            "../other/main.wren",
+            "/etc/passwd",
+            "scripts/../../main.wren",
+            "''",
+        ] {
+            let err = load(&format!(
+                "name: core\nversion: 0.1.0\nentrypoint: {entrypoint}\n"
+            ))
+            .unwrap_err();
+            assert!(
+                matches!(err.kind, ManifestErrorKind::InvalidEntrypoint { .. }),
+                "{entrypoint}: {err}"
+            );
+            assert_eq!(err.key.as_deref(), Some("entrypoint"));
+        }
+    }
+}
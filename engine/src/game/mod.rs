@@ -1,97 +1,943 @@
+mod api;
+mod assets;
+mod data;
+mod lang;
+mod lock;
+mod manifest;
+mod profile;
+mod registry;
+mod resolve;
+mod script;
+mod settings;
+mod source;
+#[cfg(test)]
+mod testing;
+mod watch;
+mod watchdog;
+
+pub use data::DataError;
+pub use lang::LangError;
+pub use lock::{LockError, LockedModule, Lockfile};
+pub use manifest::{Manifest, ManifestError};
+pub use profile::{Profile, ProfileError};
+pub use registry::ContentKind;
+pub use resolve::ResolveError;
+pub use script::ScriptError;
+pub use settings::{Settings, SettingsError};
+pub use source::{Asset, ModuleSource};
+pub use watch::ModuleWatcher;
+
+use script::{RegistryGuard, ScriptHost};
+
 use semver::{Version, VersionReq};
-use std::{fs, io::Read, path::PathBuf};
-use yaml_rust::{Yaml, YamlLoader};
+use std::{fmt, fs, io, mem, path::PathBuf};
+use yaml_rust::{yaml::Hash, Yaml, YamlEmitter};
 
-struct ModuleReq {
+#[derive(Debug)]
+pub enum ModuleReqError {
+    MissingName {
+        line: String,
+    },
+    InvalidName {
+        line: String,
+        name: String,
+    },
+    InvalidVersionReq {
+        line: String,
+        version_req: String,
+        error: semver::Error,
+    },
+}
+
+impl fmt::Display for ModuleReqError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::MissingName { line } => write!(f, "missing module name in `{line}`"),
+            Self::InvalidName { line, name } => write!(
+                f,
+                "invalid module name `{name}` in `{line}` (expected letters, digits, `-` or `_`)"
+            ),
+            Self::InvalidVersionReq {
+                line,
+                version_req,
+                error,
+            } => write!(
+                f,
+                "invalid version requirement `{version_req}` in `{line}`: {error}"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ModuleReqError {}
+
+// Maps are merged key by key, anything else in `overlay` replaces what's in
+// `base`.
+fn merge_yaml(base: &mut Hash, overlay: Hash) {
+    for (key, value) in overlay {
+        match (base.get_mut(&key), value) {
+            (Some(Yaml::Hash(base)), Yaml::Hash(overlay)) => merge_yaml(base, overlay),
+            (_, value) => {
+                base.insert(key, value);
+            }
+        }
+    }
+}
+
+fn is_valid_name(name: &str) -> bool {
+    !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+}
+
+#[derive(Clone, Debug)]
+pub struct ModuleReq {
     name: String,
     version_req: VersionReq,
 }
 
 impl ModuleReq {
-    fn parse(string: String) -> Self {
-        todo!()
+    // Accepts `name`, `name@<req>` and `name <req>`, where `<req>` is anything
+    // `semver::VersionReq` understands, e.g. `core@^0.1` or `core >=0.1, <0.3`.
+    pub fn parse(string: String) -> Result<Self, ModuleReqError> {
+        let line = string.trim();
+
+        let (name, version_req) = match line.find(|c: char| c == '@' || c.is_whitespace()) {
+            Some(idx) => {
+                let (name, rest) = line.split_at(idx);
+                let rest = rest.trim_start();
+                (name, rest.strip_prefix('@').unwrap_or(rest).trim())
+            }
+            None => (line, ""),
+        };
+
+        if name.is_empty() {
+            return Err(ModuleReqError::MissingName {
+                line: string.clone(),
+            });
+        }
+
+        if !is_valid_name(name) {
+            return Err(ModuleReqError::InvalidName {
+                line: string.clone(),
+                name: name.to_string(),
+            });
+        }
+
+        let version_req = if version_req.is_empty() {
+            VersionReq::STAR
+        } else {
+            VersionReq::parse(version_req).map_err(|error| ModuleReqError::InvalidVersionReq {
+                line: string.clone(),
+                version_req: version_req.to_string(),
+                error,
+            })?
+        };
+
+        Ok(Self {
+            name: name.to_string(),
+            version_req,
+        })
+    }
+
+    #[cfg(test)]
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    #[cfg(test)]
+    pub fn version_req(&self) -> &VersionReq {
+        &self.version_req
+    }
+
+    pub fn matches(&self, name: &str, version: &Version) -> bool {
+        self.name == name && self.version_req.matches(version)
     }
 }
 
-struct PreloadModule {
-    name: String,
-    version: Version,
-    dependencies: Vec<ModuleReq>,
+impl fmt::Display for ModuleReq {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {}", self.name, self.version_req)
+    }
+}
+
+#[derive(Debug)]
+pub enum PreloadError {
+    Manifest(ManifestError),
+    IncompatibleEngine {
+        name: String,
+        version: Version,
+        engine_req: VersionReq,
+        source: ModuleSource,
+    },
+    ArchiveName {
+        source: ModuleSource,
+        expected: String,
+    },
+    Duplicate {
+        module: Box<PreloadModule>,
+        existing: ModuleSource,
+    },
+}
+
+impl fmt::Display for PreloadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Manifest(e) => write!(f, "{e}"),
+            Self::IncompatibleEngine {
+                name,
+                version,
+                engine_req,
+                source,
+            } => write!(
+                f,
+                "{name} {version} ({source}) requires engine {engine_req}, but this is engine {}",
+                engine_version()
+            ),
+            Self::ArchiveName { source, expected } => write!(
+                f,
+                "{source}: module archives must be named `{expected}` after the module they contain"
+            ),
+            Self::Duplicate { module, existing } => write!(
+                f,
+                "{}: {} {} is already provided by {existing}",
+                module.source, module.manifest.name, module.manifest.version
+            ),
+        }
+    }
+}
+
+impl std::error::Error for PreloadError {}
+
+impl From<ManifestError> for PreloadError {
+    fn from(e: ManifestError) -> Self {
+        Self::Manifest(e)
+    }
+}
+
+pub fn engine_version() -> Version {
+    Version::parse(env!("CARGO_PKG_VERSION")).unwrap()
+}
 
-    dir: PathBuf,
+#[derive(Clone, Debug)]
+pub struct PreloadModule {
+    manifest: Manifest,
+
+    source: ModuleSource,
 }
 
 impl PreloadModule {
-    fn new(dir: PathBuf) -> Self {
-        todo!()
+    pub fn name(&self) -> &str {
+        &self.manifest.name
+    }
+
+    fn new(source: ModuleSource) -> Result<Self, PreloadError> {
+        let manifest = Manifest::load(&source)?;
+
+        // An archive is the unit modules get passed around as, so its name has
+        // to say exactly which module version is inside.
+        if let ModuleSource::Archive(path) = &source {
+            let expected = format!(
+                "{}-{}.{}",
+                manifest.name,
+                manifest.version,
+                source::ARCHIVE_EXTENSION
+            );
+            if path
+                .file_name()
+                .is_some_and(|name| name != expected.as_str())
+            {
+                return Err(PreloadError::ArchiveName { source, expected });
+            }
+        }
+
+        if !manifest.engine.matches(&engine_version()) {
+            return Err(PreloadError::IncompatibleEngine {
+                name: manifest.name,
+                version: manifest.version,
+                engine_req: manifest.engine,
+                source,
+            });
+        }
+
+        Ok(Self { manifest, source })
     }
 }
 
-struct Module {}
+pub struct Module {
+    manifest: Manifest,
+    source: ModuleSource,
+}
 
 impl Module {
-    fn load(preload: PreloadModule) -> Self {
-        todo!()
+    fn load(
+        preload: &PreloadModule,
+        settings: Settings,
+        script_host: &ScriptHost,
+    ) -> Result<Self, LoadError> {
+        let manifest = &preload.manifest;
+
+        // Registered before the entrypoint runs, so scripts can still replace
+        // them under the same key.
+        let textures = assets::find_textures(&manifest.name, &preload.source).map_err(|error| {
+            LoadError::Assets {
+                module: manifest.name.clone(),
+                error,
+            }
+        })?;
+        for (key, asset) in textures {
+            script_host.registry().register_texture(key, asset);
+        }
+
+        data::load(
+            &manifest.name,
+            &preload.source,
+            script_host.registry().data_mut(),
+        )?;
+
+        script_host
+            .registry()
+            .translations_mut()
+            .load(&preload.source)?;
+
+        script_host.run_entrypoint(manifest, &preload.source, settings)?;
+
+        Ok(Self {
+            manifest: manifest.clone(),
+            source: preload.source.clone(),
+        })
+    }
+
+    pub fn name(&self) -> &str {
+        &self.manifest.name
+    }
+
+    pub fn version(&self) -> &Version {
+        &self.manifest.version
+    }
+
+    pub fn manifest(&self) -> &Manifest {
+        &self.manifest
+    }
+
+    pub fn source(&self) -> &ModuleSource {
+        &self.source
     }
 }
 
-pub struct Profile {
-    name: String,
-    modules: Vec<ModuleReq>,
+#[derive(Debug)]
+pub enum LoadError {
+    Preload(PreloadError),
+    Resolve(ResolveError),
+    Script(ScriptError),
+    Settings(SettingsError),
+    Data(DataError),
+    Lang(LangError),
+    Assets {
+        module: String,
+        error: io::Error,
+    },
+    Texture {
+        key: String,
+        asset: Box<Asset>,
+        error: image::ImageError,
+    },
+    Lock(LockError),
+    Hash {
+        module: String,
+        error: io::Error,
+    },
+    HashMismatch {
+        module: Box<LockedModule>,
+        actual: String,
+        source: ModuleSource,
+        lock_path: PathBuf,
+    },
+    VersionMismatch {
+        module: Box<LockedModule>,
+        resolved: Version,
+        lock_path: PathBuf,
+    },
 }
 
-impl Profile {
-    pub fn load(path: PathBuf) -> Self {
-        let mut yaml_file = fs::File::open(path.join("profile.yml")).unwrap();
-        let mut yaml_str = String::new();
-        yaml_file.read_to_string(&mut yaml_str).unwrap();
+impl fmt::Display for LoadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Preload(e) => write!(f, "failed to reload module: {e}"),
+            Self::Resolve(e) => write!(f, "failed to resolve modules: {e}"),
+            Self::Script(e) => write!(f, "failed to load {e}"),
+            Self::Settings(e) => write!(f, "invalid {e}"),
+            Self::Data(e) => write!(f, "failed to load data: {e}"),
+            Self::Lang(e) => write!(f, "failed to load translations: {e}"),
+            Self::Assets { module, error } => {
+                write!(f, "failed to load assets of module {module}: {error}")
+            }
+            Self::Texture { key, asset, error } => {
+                write!(f, "failed to load texture {key} ({asset}): {error}")
+            }
+            Self::Lock(e) => write!(f, "failed to read lockfile: {e}"),
+            Self::Hash { module, error } => write!(f, "failed to hash module {module}: {error}"),
+            Self::HashMismatch {
+                module,
+                actual,
+                source,
+                lock_path,
+            } => write!(
+                f,
+                "{} {} ({source}) doesn't match {}: expected {}, found {actual}. \
+                 Pass --update-lock to accept the module as it is now",
+                module.name,
+                module.version,
+                lock_path.display(),
+                module.hash
+            ),
+            Self::VersionMismatch {
+                module,
+                resolved,
+                lock_path,
+            } => write!(
+                f,
+                "{} is locked to {} in {}, but resolved to {resolved}. \
+                 Pass --update-lock to accept the new version",
+                module.name,
+                module.version,
+                lock_path.display(),
+            ),
+        }
+    }
+}
 
-        let yaml = &YamlLoader::load_from_str(&yaml_str).unwrap()[0];
+impl std::error::Error for LoadError {}
 
-        let name = match yaml["name"] {
-            Yaml::String(ref s) => s.clone(),
-            _ => panic!("invalid name"),
-        };
-        let modules = match &yaml["modules"] {
-            Yaml::Array(arr) => arr
-                .into_iter()
-                .map(|module| match module {
-                    Yaml::String(ref s) => s.clone(),
-                    _ => panic!("invalid module"),
-                })
-                .collect::<Vec<String>>(),
-            _ => panic!("invalid module list"),
-        }
-        .into_iter()
-        .map(|s| ModuleReq::parse(s))
-        .collect();
+impl From<PreloadError> for LoadError {
+    fn from(e: PreloadError) -> Self {
+        Self::Preload(e)
+    }
+}
+
+impl From<ResolveError> for LoadError {
+    fn from(e: ResolveError) -> Self {
+        Self::Resolve(e)
+    }
+}
+
+impl From<LockError> for LoadError {
+    fn from(e: LockError) -> Self {
+        Self::Lock(e)
+    }
+}
+
+impl From<ScriptError> for LoadError {
+    fn from(e: ScriptError) -> Self {
+        Self::Script(e)
+    }
+}
 
-        Self { name, modules }
+impl From<DataError> for LoadError {
+    fn from(e: DataError) -> Self {
+        Self::Data(e)
+    }
+}
+
+impl From<LangError> for LoadError {
+    fn from(e: LangError) -> Self {
+        Self::Lang(e)
+    }
+}
+
+impl From<SettingsError> for LoadError {
+    fn from(e: SettingsError) -> Self {
+        Self::Settings(e)
     }
 }
 
 pub struct Game {
     pub profile: Profile,
+    // The locale player-facing text is translated to, e.g. `en-GB`.
+    locale: String,
 
     module_pool: Vec<PreloadModule>,
+    modules: Vec<Module>,
+
+    script_host: ScriptHost,
 }
 
 impl Game {
-    pub fn new(profile: Profile, modules_dir: PathBuf) -> Self {
-        let module_pool = fs::read_dir(modules_dir)
+    pub fn new(profile: Profile, modules_dir: PathBuf, locale: String) -> Self {
+        let mut sources = fs::read_dir(modules_dir)
             .unwrap()
             .filter_map(|res| res.ok())
-            .filter(|entry| entry.file_type().unwrap().is_dir())
-            .map(|entry| PreloadModule::new(entry.path()))
-            .collect();
+            .filter_map(|entry| {
+                let path = entry.path();
+                if entry.file_type().unwrap().is_dir() {
+                    Some(ModuleSource::Dir(path))
+                } else if path
+                    .extension()
+                    .is_some_and(|ext| ext == source::ARCHIVE_EXTENSION)
+                {
+                    Some(ModuleSource::Archive(path))
+                } else {
+                    None
+                }
+            })
+            .collect::<Vec<_>>();
+        sources.sort_by(|a, b| a.path().cmp(b.path()));
+
+        let mut module_pool: Vec<PreloadModule> = vec![];
+        for source in sources {
+            let module = PreloadModule::new(source).and_then(|module| {
+                match module_pool.iter().find(|other| {
+                    other.manifest.name == module.manifest.name
+                        && other.manifest.version == module.manifest.version
+                }) {
+                    Some(existing) => Err(PreloadError::Duplicate {
+                        module: Box::new(module),
+                        existing: existing.source.clone(),
+                    }),
+                    None => Ok(module),
+                }
+            });
+
+            match module {
+                Ok(module) => module_pool.push(module),
+                Err(e) => eprintln!("warning: skipping module: {e}"),
+            }
+        }
 
         Self {
-            profile,
             module_pool,
+            modules: vec![],
+            script_host: ScriptHost::new(&profile, &locale),
+            profile,
+            locale,
         }
     }
 
-    fn match_modules(&self, module_req: ModuleReq) -> Vec<Module> {
-        todo!() // find all modules that match the module req
+    pub fn resolve_modules(
+        &self,
+        lockfile: Option<&Lockfile>,
+    ) -> Result<Vec<&PreloadModule>, ResolveError> {
+        let locked = lockfile
+            .map(|lockfile| {
+                lockfile
+                    .modules
+                    .iter()
+                    .map(|module| (module.name.clone(), module.version.clone()))
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        let selected = resolve::resolve(&self.profile.modules, &self.module_pool, &locked)?;
+        let order = resolve::load_order(&selected, &self.module_pool)?;
+
+        Ok(order
+            .into_iter()
+            .map(|idx| &self.module_pool[idx])
+            .collect())
+    }
+
+    // Resolves the profile, preferring the versions in its lockfile, and
+    // checks every module that is still locked against its recorded version
+    // and hash before writing the lockfile back out. Modules in `edited` were
+    // changed while the game was running, so they are taken as they are, and
+    // with `update_lock` every module is.
+    fn lock_modules(
+        &self,
+        edited: &[PathBuf],
+        update_lock: bool,
+    ) -> Result<Vec<&PreloadModule>, LoadError> {
+        let lock_path = self.profile.lock_path();
+        let lockfile = Lockfile::load(&lock_path)?;
+
+        let resolved = self.resolve_modules(lockfile.as_ref())?;
+
+        let mut new_lockfile = Lockfile::default();
+        for preload in &resolved {
+            let manifest = &preload.manifest;
+            let hash = lock::hash_module(&preload.source).map_err(|error| LoadError::Hash {
+                module: manifest.name.clone(),
+                error,
+            })?;
+
+            let locked = lockfile
+                .as_ref()
+                .and_then(|lockfile| lockfile.get(&manifest.name));
+            let accepted = update_lock || edited.iter().any(|path| path == preload.source.path());
+            if let Some(locked) = locked.filter(|_| !accepted) {
+                if locked.version != manifest.version {
+                    return Err(LoadError::VersionMismatch {
+                        module: Box::new(locked.clone()),
+                        resolved: manifest.version.clone(),
+                        lock_path,
+                    });
+                }
+                if locked.hash != hash {
+                    return Err(LoadError::HashMismatch {
+                        module: Box::new(locked.clone()),
+                        actual: hash,
+                        source: preload.source.clone(),
+                        lock_path,
+                    });
+                }
+            }
+
+            new_lockfile.modules.push(LockedModule {
+                name: manifest.name.clone(),
+                version: manifest.version.clone(),
+                hash,
+            });
+        }
+
+        if lockfile.as_ref() != Some(&new_lockfile) {
+            if let Err(e) = new_lockfile.save(&lock_path) {
+                eprintln!("warning: failed to write {}: {e}", lock_path.display());
+            }
+        }
+
+        Ok(resolved)
+    }
+
+    // Loads the modules the profile resolves to. With `update_lock`, modules
+    // that no longer match the lockfile are accepted and the lockfile is
+    // rewritten.
+    pub fn load_modules(&mut self, update_lock: bool) -> Result<(), LoadError> {
+        self.load_modules_with(&[], update_lock)
+    }
+
+    fn load_modules_with(
+        &mut self,
+        edited: &[PathBuf],
+        update_lock: bool,
+    ) -> Result<(), LoadError> {
+        let preloads = self.lock_modules(edited, update_lock)?;
+
+        for name in self.profile.settings.keys().filter_map(Yaml::as_str) {
+            if !preloads.iter().any(|preload| preload.name() == name) {
+                eprintln!("warning: profile has settings for {name}, which isn't loaded");
+            }
+        }
+
+        // Checked before any script runs, so a typo in the profile doesn't
+        // leave half of the modules loaded.
+        let settings = preloads
+            .iter()
+            .map(|preload| {
+                let values = self
+                    .profile
+                    .settings
+                    .get(&Yaml::String(preload.name().to_string()))
+                    .unwrap_or(&Yaml::Null);
+                settings::validate(preload.name(), preload.manifest.settings.as_ref(), values)
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let modules = preloads
+            .into_iter()
+            .zip(settings)
+            .map(|(preload, settings)| Module::load(preload, settings, &self.script_host))
+            .collect::<Result<_, _>>()?;
+
+        self.modules = modules;
+
+        Ok(())
+    }
+
+    // Reloads every module after the module sources in `edited` changed on
+    // disk. All modules are reloaded on a fresh script host rather than just
+    // the edited ones, since later modules can replace what earlier ones
+    // registered. If anything fails the previously loaded modules are kept.
+    pub fn reload(&mut self, edited: &[PathBuf]) -> Result<(), LoadError> {
+        let old_pool = self.module_pool.clone();
+        let old_modules = mem::take(&mut self.modules);
+        let old_host = mem::replace(
+            &mut self.script_host,
+            ScriptHost::new(&self.profile, &self.locale),
+        );
+
+        let result = self
+            .reread_manifests(edited)
+            .and_then(|()| self.load_modules_with(edited, false));
+
+        if result.is_err() {
+            self.module_pool = old_pool;
+            self.modules = old_modules;
+            self.script_host = old_host;
+        }
+
+        result
+    }
+
+    fn reread_manifests(&mut self, edited: &[PathBuf]) -> Result<(), LoadError> {
+        for preload in &mut self.module_pool {
+            if edited.iter().any(|path| path == preload.source.path()) {
+                *preload = PreloadModule::new(preload.source.clone())?;
+            }
+        }
+
+        Ok(())
+    }
+
+    // Lists the options of every module the profile resolves to as Markdown,
+    // for `--dump-settings`. Unlike `load_modules` this doesn't touch the
+    // lockfile or run any scripts.
+    pub fn dump_settings(&self) -> Result<String, LoadError> {
+        use std::fmt::Write;
+
+        let lockfile = Lockfile::load(&self.profile.lock_path())?;
+        let mut out = String::new();
+
+        for preload in self.resolve_modules(lockfile.as_ref())? {
+            let manifest = &preload.manifest;
+            writeln!(out, "## {} {}\n", manifest.name, manifest.version).unwrap();
+
+            match &manifest.settings {
+                None => writeln!(out, "Doesn't declare its settings.\n").unwrap(),
+                Some(schema) if schema.is_empty() => writeln!(out, "No settings.\n").unwrap(),
+                Some(schema) => {
+                    writeln!(out, "| Option | Type | Default | Allowed | Description |").unwrap();
+                    writeln!(out, "| --- | --- | --- | --- | --- |").unwrap();
+                    for (option, declared) in schema {
+                        writeln!(
+                            out,
+                            "| `{option}` | {} | {} | {} | {} |",
+                            declared.ty,
+                            declared
+                                .default
+                                .as_ref()
+                                .map(|default| format!("`{default}`"))
+                                .unwrap_or_default(),
+                            declared.allowed(),
+                            declared.description.as_deref().unwrap_or_default(),
+                        )
+                        .unwrap();
+                    }
+                    writeln!(out).unwrap();
+                }
+            }
+        }
+
+        Ok(out)
+    }
+
+    // The data tables of every loaded module with all patches applied, as a
+    // YAML document, for `--dump-data`.
+    pub fn dump_data(&self) -> String {
+        let tables = self
+            .registry()
+            .data()
+            .iter()
+            .map(|(table, entries)| {
+                let entries = entries
+                    .iter()
+                    .map(|(key, value)| (Yaml::String(key.clone()), value.clone()))
+                    .collect();
+                (Yaml::String(table.clone()), Yaml::Hash(entries))
+            })
+            .collect();
+
+        let mut out = String::new();
+        YamlEmitter::new(&mut out)
+            .dump(&Yaml::Hash(tables))
+            .unwrap();
+        out.push('\n');
+
+        out
+    }
+
+    // The loaded modules in the order they were loaded in, with their
+    // descriptions and authors, for `--list-modules`.
+    pub fn list_modules(&self) -> String {
+        use std::fmt::Write;
+
+        let mut out = String::new();
+        writeln!(
+            out,
+            "{}: {} modules",
+            self.profile.name(),
+            self.modules.len()
+        )
+        .unwrap();
+
+        for module in &self.modules {
+            let manifest = module.manifest();
+            writeln!(
+                out,
+                "{} {} ({})",
+                module.name(),
+                module.version(),
+                module.source
+            )
+            .unwrap();
+            if let Some(description) = &manifest.description {
+                writeln!(out, "    {description}").unwrap();
+            }
+            if !manifest.authors.is_empty() {
+                writeln!(out, "    by {}", manifest.authors.join(", ")).unwrap();
+            }
+        }
+
+        out
+    }
+
+    // Every block, item and entity registered by the loaded modules, with the
+    // module that registered it, for `--dump-content`.
+    pub fn dump_content(&self) -> String {
+        use std::fmt::Write;
+
+        let registry = self.registry();
+        let mut out = String::new();
+
+        for kind in [ContentKind::Block, ContentKind::Item, ContentKind::Entity] {
+            for (id, module) in registry.content(kind) {
+                writeln!(out, "{kind} {id} ({module})").unwrap();
+            }
+        }
+
+        out
+    }
+
+    // For every locale some loaded module has text in, the keys that would
+    // show up untranslated, for `--lang-report`.
+    pub fn lang_report(&self) -> String {
+        use std::fmt::Write;
+
+        let registry = self.registry();
+        let mut out = String::new();
+
+        for (locale, missing) in registry.translations().missing() {
+            if missing.is_empty() {
+                writeln!(out, "{locale}: complete").unwrap();
+                continue;
+            }
+
+            writeln!(out, "{locale}: {} missing", missing.len()).unwrap();
+            for key in missing {
+                writeln!(out, "    {key}").unwrap();
+            }
+        }
+
+        out
+    }
+
+    pub fn modules(&self) -> &[Module] {
+        &self.modules
+    }
+
+    pub fn registry(&self) -> RegistryGuard<'_> {
+        self.script_host.registry()
+    }
+
+    pub fn load_textures(&self) -> Result<Vec<(String, image::RgbaImage)>, LoadError> {
+        self.registry()
+            .textures()
+            .iter()
+            .map(|(key, asset)| {
+                let error = |error| LoadError::Texture {
+                    key: key.clone(),
+                    asset: Box::new(asset.clone()),
+                    error,
+                };
+
+                let bytes = asset
+                    .read()
+                    .map_err(|e| error(image::ImageError::IoError(e)))?;
+                let image = image::load_from_memory(&bytes).map_err(error)?;
+
+                Ok((key.clone(), image.into_rgba8()))
+            })
+            .collect()
+    }
+
+    pub fn dispatch_event(&self, event: &str, args: &[&str]) -> Result<(), ScriptError> {
+        self.script_host.dispatch_event(event, args)
+    }
+
+    pub fn run_command(&self, name: &str, args: &[&str]) -> Result<(), ScriptError> {
+        self.script_host.run_command(name, args)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::testing::TempDir;
+
+    fn parse(s: &str) -> ModuleReq {
+        ModuleReq::parse(s.to_string()).unwrap()
+    }
+
+    #[test]
+    fn module_req_forms() {
+        let req = parse("core");
+        assert_eq!(req.name(), "core");
+        assert_eq!(*req.version_req(), VersionReq::STAR);
+
+        for s in ["core@^0.1", "core ^0.1", "core @ ^0.1", "  core@^0.1  "] {
+            let req = parse(s);
+            assert_eq!(req.name(), "core", "{s}");
+            assert_eq!(req.version_req().to_string(), "^0.1", "{s}");
+        }
+
+        let req = parse("core >=0.1, <0.3");
+        assert_eq!(req.version_req().to_string(), ">=0.1, <0.3");
+        assert!(req.matches("core", &Version::new(0, 2, 5)));
+        assert!(!req.matches("core", &Version::new(0, 3, 0)));
+        assert!(!req.matches("other", &Version::new(0, 2, 0)));
+    }
+
+    #[test]
+    fn module_req_errors() {
+        let err = |s: &str| ModuleReq::parse(s.to_string()).unwrap_err();
+
+        assert!(matches!(err(""), ModuleReqError::MissingName { .. }));
+        assert!(matches!(err("@^0.1"), ModuleReqError::MissingName { .. }));
+        assert!(matches!(
+            err("co/re"),
+            ModuleReqError::InvalidName { name, .. } if name == "co/re"
+        ));
+        assert!(matches!(
+            err("core@not-a-version"),
+            ModuleReqError::InvalidVersionReq { version_req, .. } if version_req == "not-a-version"
+        ));
+    }
+
+    #[test]
+    fn locked_versions_are_kept_unless_updated() {
+        let dir = TempDir::new();
+        dir.write("profile/profile.yml", "name: test\nmodules:\n  - core\n");
+        dir.write("modules/core/module.yml", "name: core\nversion: 1.0.0\n");
+        dir.write("modules/core/main.wren", "");
+
+        let load = |update_lock| {
+            let profile = Profile::load(dir.path().join("profile")).unwrap();
+            let mut game = Game::new(profile, dir.path().join("modules"), "en".to_string());
+            game.load_modules(update_lock).map(|()| game)
+        };
+        let locked_version = |game: &Game| {
+            Lockfile::load(&game.profile.lock_path())
+                .unwrap()
+                .unwrap()
+                .modules[0]
+                .version
+                .clone()
+        };
+
+        let game = load(false).unwrap();
+        assert_eq!(locked_version(&game), Version::new(1, 0, 0));
+
+        // The locked version is gone.
+        dir.write("modules/core/module.yml", "name: core\nversion: 1.1.0\n");
+        let Err(err) = load(false) else {
+            panic!("loaded a version that isn't locked");
+        };
+        assert!(
+            matches!(&err, LoadError::VersionMismatch { resolved, .. } if *resolved == Version::new(1, 1, 0)),
+            "{err}"
+        );
+        assert_eq!(locked_version(&game), Version::new(1, 0, 0));
+
+        let game = load(true).unwrap();
+        assert_eq!(locked_version(&game), Version::new(1, 1, 0));
     }
 }
@@ -0,0 +1,564 @@
+use std::{
+    fmt, fs, io,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+use yaml_rust::{yaml::Hash, Yaml, YamlLoader};
+
+use super::{lock, merge_yaml, watchdog::ScriptBudget, ModuleReq, ModuleReqError};
+
+pub const PROFILE_FILE: &str = "profile.yml";
+pub const MODULE_DATA_DIR: &str = "module_data";
+
+const BUDGET_EXPECTED: &str = "a map with `load_ms` and `handler_ms` in whole milliseconds";
+
+const KEYS: &[&str] = &[
+    "name",
+    "extends",
+    "modules",
+    "remove_modules",
+    "settings",
+    "script_budget",
+];
+
+#[derive(Debug)]
+pub enum ProfileErrorKind {
+    Missing,
+    Io(io::Error),
+    Syntax(yaml_rust::ScanError),
+    Empty,
+    NotAMap,
+    UnknownKey(String),
+    MissingKey(&'static str),
+    WrongType {
+        key: &'static str,
+        expected: &'static str,
+    },
+    InvalidModuleReq(Box<ModuleReqError>),
+    RemovesUnknownModule(String),
+    ExtendsCycle(Vec<PathBuf>),
+}
+
+#[derive(Debug)]
+pub struct ProfileError {
+    pub path: PathBuf,
+    // The contents of the profile, if it could be read.
+    pub source: Option<String>,
+    // 1-based line and column of the error, if known.
+    pub location: Option<(usize, usize)>,
+    pub kind: ProfileErrorKind,
+}
+
+impl fmt::Display for ProfileError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.path.display())?;
+        if let Some((line, column)) = self.location {
+            write!(f, ":{line}:{column}")?;
+        }
+
+        match &self.kind {
+            ProfileErrorKind::Missing => write!(f, ": profile not found"),
+            ProfileErrorKind::Io(e) => write!(f, ": {e}"),
+            ProfileErrorKind::Syntax(e) => write!(f, ": {e}"),
+            ProfileErrorKind::Empty => write!(f, ": file is empty"),
+            ProfileErrorKind::NotAMap => write!(f, ": expected a map at the top level"),
+            ProfileErrorKind::UnknownKey(key) => write!(f, ": `{key}` is not a known key"),
+            ProfileErrorKind::MissingKey(key) => write!(f, ": `{key}` is required"),
+            ProfileErrorKind::WrongType { key, expected } => {
+                write!(f, ": `{key}`: expected {expected}")
+            }
+            ProfileErrorKind::InvalidModuleReq(e) => write!(f, ": {e}"),
+            ProfileErrorKind::RemovesUnknownModule(name) => {
+                write!(
+                    f,
+                    ": can't remove `{name}`, the parent profile doesn't have it"
+                )
+            }
+            ProfileErrorKind::ExtendsCycle(chain) => {
+                let chain = chain
+                    .iter()
+                    .map(|path| path.display().to_string())
+                    .collect::<Vec<_>>()
+                    .join(" -> ");
+                write!(f, ": profile extends itself: {chain}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for ProfileError {}
+
+pub struct Profile {
+    name: String,
+    pub(super) modules: Vec<ModuleReq>,
+    // Settings for each module, keyed by module name. Merged over the ones of
+    // the profile this extends.
+    pub(super) settings: Hash,
+    pub(super) script_budget: ScriptBudget,
+
+    dir: PathBuf,
+}
+
+// A single `profile.yml`, before it's merged with the profile it extends.
+struct ProfileFile {
+    name: String,
+    extends: Option<PathBuf>,
+    modules: Vec<ModuleReq>,
+    remove_modules: Vec<(String, Option<(usize, usize)>)>,
+    settings: Hash,
+    // `load_ms` and `handler_ms`, each only if the file sets it.
+    script_budget: (Option<Duration>, Option<Duration>),
+
+    path: PathBuf,
+    source: String,
+}
+
+impl Profile {
+    pub fn load(dir: PathBuf) -> Result<Self, ProfileError> {
+        Self::load_extending(dir, &mut vec![])
+    }
+
+    fn load_extending(dir: PathBuf, chain: &mut Vec<PathBuf>) -> Result<Self, ProfileError> {
+        let file = ProfileFile::load(&dir)?;
+        let error = |location, kind| ProfileError {
+            path: file.path.clone(),
+            source: Some(file.source.clone()),
+            location,
+            kind,
+        };
+
+        let Some(extends) = &file.extends else {
+            return Ok(Self {
+                script_budget: override_budget(ScriptBudget::default(), file.script_budget),
+                name: file.name,
+                modules: file.modules,
+                settings: file.settings,
+                dir,
+            });
+        };
+
+        // Compare canonical paths, so `../a` and `../../x/a` are the same profile.
+        let canonical = dir.canonicalize().unwrap_or_else(|_| dir.clone());
+        chain.push(canonical);
+
+        let parent_dir = dir.join(extends);
+        let parent_canonical = parent_dir
+            .canonicalize()
+            .unwrap_or_else(|_| parent_dir.clone());
+        if let Some(start) = chain.iter().position(|p| *p == parent_canonical) {
+            let mut cycle = chain[start..].to_vec();
+            cycle.push(parent_canonical);
+            return Err(error(
+                find_key(&file.source, "extends"),
+                ProfileErrorKind::ExtendsCycle(cycle),
+            ));
+        }
+
+        let parent = Self::load_extending(parent_dir, chain)?;
+
+        let mut modules = parent.modules;
+        for (name, location) in &file.remove_modules {
+            let len = modules.len();
+            modules.retain(|req| &req.name != name);
+            if modules.len() == len {
+                return Err(error(
+                    *location,
+                    ProfileErrorKind::RemovesUnknownModule(name.clone()),
+                ));
+            }
+        }
+        for req in file.modules {
+            match modules.iter_mut().find(|other| other.name == req.name) {
+                Some(other) => *other = req,
+                None => modules.push(req),
+            }
+        }
+
+        let script_budget = override_budget(parent.script_budget, file.script_budget);
+
+        let mut settings = parent.settings;
+        merge_yaml(&mut settings, file.settings);
+
+        Ok(Self {
+            name: file.name,
+            modules,
+            settings,
+            script_budget,
+            dir,
+        })
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn lock_path(&self) -> PathBuf {
+        self.dir.join(lock::LOCK_FILE)
+    }
+
+    // Where modules with the `filesystem` permission keep their files, in a
+    // directory per module.
+    pub fn module_data_dir(&self) -> PathBuf {
+        self.dir.join(MODULE_DATA_DIR)
+    }
+}
+
+impl ProfileFile {
+    fn load(dir: &Path) -> Result<Self, ProfileError> {
+        let path = dir.join(PROFILE_FILE);
+
+        let yaml_str = fs::read_to_string(&path).map_err(|e| ProfileError {
+            path: path.clone(),
+            source: None,
+            location: None,
+            kind: match e.kind() {
+                io::ErrorKind::NotFound => ProfileErrorKind::Missing,
+                _ => ProfileErrorKind::Io(e),
+            },
+        })?;
+        let error = |location, kind| ProfileError {
+            path: path.clone(),
+            source: Some(yaml_str.clone()),
+            location,
+            kind,
+        };
+        let wrong_type = |key, expected| {
+            error(
+                find_key(&yaml_str, key),
+                ProfileErrorKind::WrongType { key, expected },
+            )
+        };
+
+        let yaml = YamlLoader::load_from_str(&yaml_str)
+            .map_err(|e| {
+                let marker = e.marker();
+                let location = Some((marker.line(), marker.col() + 1));
+                error(location, ProfileErrorKind::Syntax(e))
+            })?
+            .into_iter()
+            .next()
+            .ok_or_else(|| error(None, ProfileErrorKind::Empty))?;
+
+        let Yaml::Hash(ref hash) = yaml else {
+            return Err(error(Some((1, 1)), ProfileErrorKind::NotAMap));
+        };
+        for key in hash.keys() {
+            match key {
+                Yaml::String(s) if KEYS.contains(&s.as_str()) => {}
+                Yaml::String(s) => {
+                    return Err(error(
+                        find_key(&yaml_str, s),
+                        ProfileErrorKind::UnknownKey(s.clone()),
+                    ))
+                }
+                _ => return Err(error(None, ProfileErrorKind::NotAMap)),
+            }
+        }
+
+        let name = match &yaml["name"] {
+            Yaml::String(s) => s.clone(),
+            Yaml::BadValue => return Err(error(None, ProfileErrorKind::MissingKey("name"))),
+            _ => return Err(wrong_type("name", "a string")),
+        };
+
+        let extends = match &yaml["extends"] {
+            Yaml::String(s) => Some(PathBuf::from(s)),
+            Yaml::BadValue => None,
+            _ => return Err(wrong_type("extends", "a path to another profile")),
+        };
+
+        // A profile that extends another one only needs to list what changes.
+        let modules = match &yaml["modules"] {
+            Yaml::Array(arr) => arr
+                .iter()
+                .map(|module| match module {
+                    Yaml::String(s) => ModuleReq::parse(s.clone()).map_err(|e| {
+                        error(
+                            find_item(&yaml_str, s),
+                            ProfileErrorKind::InvalidModuleReq(Box::new(e)),
+                        )
+                    }),
+                    _ => Err(wrong_type("modules", "a list of module requirements")),
+                })
+                .collect::<Result<_, _>>()?,
+            Yaml::BadValue if extends.is_some() => vec![],
+            Yaml::BadValue => return Err(error(None, ProfileErrorKind::MissingKey("modules"))),
+            _ => return Err(wrong_type("modules", "a list of module requirements")),
+        };
+
+        let remove_modules = match &yaml["remove_modules"] {
+            Yaml::Array(arr) if extends.is_some() => arr
+                .iter()
+                .map(|module| match module {
+                    Yaml::String(s) => Ok((s.clone(), find_item(&yaml_str, s))),
+                    _ => Err(wrong_type("remove_modules", "a list of module names")),
+                })
+                .collect::<Result<_, _>>()?,
+            Yaml::BadValue => vec![],
+            _ if extends.is_none() => {
+                return Err(wrong_type(
+                    "remove_modules",
+                    "nothing, unless the profile uses `extends`",
+                ))
+            }
+            _ => return Err(wrong_type("remove_modules", "a list of module names")),
+        };
+
+        let settings = match &yaml["settings"] {
+            Yaml::Hash(hash) => hash.clone(),
+            Yaml::BadValue => Hash::new(),
+            _ => return Err(wrong_type("settings", "a map")),
+        };
+
+        let script_budget = match &yaml["script_budget"] {
+            Yaml::Hash(hash)
+                if hash
+                    .keys()
+                    .all(|key| matches!(key.as_str(), Some("load_ms" | "handler_ms"))) =>
+            {
+                let millis = |key: &str| match &yaml["script_budget"][key] {
+                    Yaml::Integer(n) if *n > 0 => Ok(Some(Duration::from_millis(*n as u64))),
+                    Yaml::BadValue => Ok(None),
+                    _ => Err(wrong_type("script_budget", BUDGET_EXPECTED)),
+                };
+                (millis("load_ms")?, millis("handler_ms")?)
+            }
+            Yaml::BadValue => (None, None),
+            _ => return Err(wrong_type("script_budget", BUDGET_EXPECTED)),
+        };
+
+        Ok(Self {
+            name,
+            extends,
+            modules,
+            remove_modules,
+            settings,
+            script_budget,
+            path,
+            source: yaml_str,
+        })
+    }
+}
+
+fn override_budget(
+    budget: ScriptBudget,
+    (load, handler): (Option<Duration>, Option<Duration>),
+) -> ScriptBudget {
+    ScriptBudget {
+        load: load.unwrap_or(budget.load),
+        handler: handler.unwrap_or(budget.handler),
+    }
+}
+
+// yaml-rust doesn't keep track of where values came from, so errors about a
+// value point at the first line that looks like it.
+fn find_key(source: &str, key: &str) -> Option<(usize, usize)> {
+    source
+        .lines()
+        .position(|line| line.starts_with(&format!("{key}:")))
+        .map(|idx| (idx + 1, 1))
+}
+
+fn find_item(source: &str, item: &str) -> Option<(usize, usize)> {
+    source.lines().enumerate().find_map(|(idx, line)| {
+        let trimmed = line.trim_start();
+        if trimmed.starts_with('-') {
+            line.find(item).map(|column| (idx + 1, column + 1))
+        } else {
+            None
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::testing::TempDir;
+
+    fn load(dir: &TempDir, profile: &str) -> Result<Profile, ProfileError> {
+        Profile::load(dir.path().join(profile))
+    }
+
+    fn modules(profile: &Profile) -> Vec<String> {
+        profile
+            .modules
+            .iter()
+            .map(|req| format!("{}@{}", req.name(), req.version_req()))
+            .collect()
+    }
+
+    // `base` <- `mid` <- `top`
+    fn chain() -> TempDir {
+        let dir = TempDir::new();
+        dir.write(
+            "base/profile.yml",
+            "name: Base\n\
+             modules:\n  - core@^0.1\n  - ui\n\
+             settings:\n  core:\n    difficulty: easy\n    seed: 1\n  ui:\n    scale: 2\n\
+             script_budget:\n  load_ms: 1000\n",
+        );
+        dir.write(
+            "mid/profile.yml",
+            "name: Mid\nextends: ../base\n\
+             remove_modules:\n  - ui\n\
+             modules:\n  - extra\n\
+             settings:\n  core:\n    difficulty: normal\n",
+        );
+        dir.write(
+            "top/profile.yml",
+            "name: Top\nextends: ../mid\n\
+             modules:\n  - core@^0.2\n\
+             settings:\n  core:\n    seed: 2\n\
+             script_budget:\n  handler_ms: 5\n",
+        );
+
+        dir
+    }
+
+    #[test]
+    fn extends_chains() {
+        let dir = chain();
+
+        let base = load(&dir, "base").unwrap();
+        assert_eq!(base.name(), "Base");
+        assert_eq!(modules(&base), ["core@^0.1", "ui@*"]);
+
+        let mid = load(&dir, "mid").unwrap();
+        assert_eq!(modules(&mid), ["core@^0.1", "extra@*"]);
+
+        let top = load(&dir, "top").unwrap();
+        assert_eq!(top.name(), "Top");
+        // Inherited modules keep their place when their requirement changes.
+        assert_eq!(modules(&top), ["core@^0.2", "extra@*"]);
+        assert_eq!(
+            top.lock_path(),
+            dir.path().join("top").join(lock::LOCK_FILE)
+        );
+    }
+
+    #[test]
+    fn extends_merges_settings_and_budgets() {
+        let top = load(&chain(), "top").unwrap();
+
+        let core = &top.settings[&Yaml::String("core".to_string())];
+        assert_eq!(core["difficulty"].as_str(), Some("normal"));
+        assert_eq!(core["seed"].as_i64(), Some(2));
+        // Settings of removed modules are kept, they're only ignored.
+        let ui = &top.settings[&Yaml::String("ui".to_string())];
+        assert_eq!(ui["scale"].as_i64(), Some(2));
+
+        assert_eq!(
+            top.script_budget,
+            ScriptBudget {
+                load: Duration::from_millis(1000),
+                handler: Duration::from_millis(5),
+            }
+        );
+    }
+
+    #[test]
+    fn removing_every_module() {
+        let dir = chain();
+        dir.write(
+            "empty/profile.yml",
+            "name: Empty\nextends: ../base\nremove_modules:\n  - core\n  - ui\n",
+        );
+
+        assert!(load(&dir, "empty").unwrap().modules.is_empty());
+    }
+
+    #[test]
+    fn removing_unknown_modules() {
+        let dir = chain();
+        dir.write(
+            "typo/profile.yml",
+            "name: Typo\nextends: ../mid\nremove_modules:\n  - ui\n",
+        );
+
+        // `mid` already removed `ui`.
+        let Err(err) = load(&dir, "typo") else {
+            panic!("removed a module that isn't inherited");
+        };
+        assert!(
+            matches!(&err.kind, ProfileErrorKind::RemovesUnknownModule(name) if name == "ui"),
+            "{err}"
+        );
+        assert_eq!(err.path, dir.path().join("typo").join(PROFILE_FILE));
+
+        dir.write(
+            "plain/profile.yml",
+            "name: Plain\nmodules: []\nremove_modules:\n  - ui\n",
+        );
+        let Err(err) = load(&dir, "plain") else {
+            panic!("removed modules without extending a profile");
+        };
+        assert!(
+            matches!(
+                err.kind,
+                ProfileErrorKind::WrongType {
+                    key: "remove_modules",
+                    ..
+                }
+            ),
+            "{err}"
+        );
+    }
+
+    #[test]
+    fn extends_cycles() {
+        let dir = TempDir::new();
+        dir.write("a/profile.yml", "name: A\nextends: ../b\n");
+        dir.write("b/profile.yml", "name: B\nextends: ../a\n");
+        dir.write("self/profile.yml", "name: Self\nextends: .\n");
+
+        let Err(err) = load(&dir, "a") else {
+            panic!("loaded a profile that extends itself");
+        };
+        let ProfileErrorKind::ExtendsCycle(cycle) = &err.kind else {
+            panic!("{err}");
+        };
+        assert_eq!(cycle.len(), 3);
+        assert_eq!(cycle.first(), cycle.last());
+        // Reported in the profile that closes the cycle.
+        assert_eq!(err.path, dir.path().join("a/../b").join(PROFILE_FILE));
+        assert_eq!(err.location, Some((2, 1)));
+
+        let Err(err) = load(&dir, "self") else {
+            panic!("loaded a profile that extends itself");
+        };
+        assert!(matches!(&err.kind, ProfileErrorKind::ExtendsCycle(cycle) if cycle.len() == 2));
+    }
+
+    #[test]
+    fn error_locations() {
+        let cases = [
+            ("name: Test\nmodules: [\n", Some((3, 1))),
+            ("- core\n", Some((1, 1))),
+            ("name: Test\nmodules: []\nmods: []\n", Some((3, 1))),
+            ("name: Test\nmodules:\n  - core\n  - co/re\n", Some((4, 5))),
+            ("name: Test\nmodules: core\n", Some((2, 1))),
+            (
+                "name: Test\nmodules: []\nscript_budget:\n  load_ms: -1\n",
+                Some((3, 1)),
+            ),
+            ("modules: []\n", None),
+        ];
+
+        for (source, location) in cases {
+            let dir = TempDir::new();
+            dir.write(Path::new("profile").join(PROFILE_FILE), source);
+
+            let Err(err) = load(&dir, "profile") else {
+                panic!("loaded {source:?}");
+            };
+            assert_eq!(err.location, location, "{source:?}: {err}");
+            assert_eq!(err.source.as_deref(), Some(source));
+        }
+
+        let Err(err) = load(&TempDir::new(), "missing") else {
+            panic!("loaded a missing profile");
+        };
+        assert!(matches!(err.kind, ProfileErrorKind::Missing));
+        assert_eq!((err.source, err.location), (None, None));
+    }
+}
@@ -0,0 +1,94 @@
+use std::{collections::BTreeMap, fmt};
+
+use super::{data::DataTables, lang::Translations, source::Asset};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum ContentKind {
+    Block,
+    Item,
+    Entity,
+}
+
+impl fmt::Display for ContentKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Block => write!(f, "block"),
+            Self::Item => write!(f, "item"),
+            Self::Entity => write!(f, "entity"),
+        }
+    }
+}
+
+// Everything modules have registered through the scripting API. Ids are
+// namespaced as `module:id`; registering an existing id again replaces it, so
+// later modules can override content from earlier ones.
+#[derive(Default, Debug)]
+pub struct Registry {
+    // (kind, id) -> module that registered it
+    content: BTreeMap<(ContentKind, String), String>,
+    textures: BTreeMap<String, Asset>,
+    // command name -> module that registered it
+    commands: BTreeMap<String, String>,
+    // Loaded from the modules' data files rather than registered by scripts.
+    data: DataTables,
+    translations: Translations,
+}
+
+impl Registry {
+    pub fn register(&mut self, kind: ContentKind, id: String, module: &str) {
+        self.content.insert((kind, id), module.to_string());
+    }
+
+    pub fn register_texture(&mut self, id: String, asset: Asset) {
+        self.textures.insert(id, asset);
+    }
+
+    pub fn register_command(&mut self, name: String, module: &str) {
+        self.commands.insert(name, module.to_string());
+    }
+
+    // Drops the commands of a module that was disabled.
+    pub fn unregister_commands(&mut self, module: &str) {
+        self.commands.retain(|_, m| m != module);
+    }
+
+    pub fn content(&self, kind: ContentKind) -> impl Iterator<Item = (&str, &str)> {
+        self.content
+            .iter()
+            .filter(move |((k, _), _)| *k == kind)
+            .map(|((_, id), module)| (id.as_str(), module.as_str()))
+    }
+
+    pub fn textures(&self) -> &BTreeMap<String, Asset> {
+        &self.textures
+    }
+
+    pub fn commands(&self) -> &BTreeMap<String, String> {
+        &self.commands
+    }
+
+    pub fn data(&self) -> &DataTables {
+        &self.data
+    }
+
+    pub fn data_mut(&mut self) -> &mut DataTables {
+        &mut self.data
+    }
+
+    pub fn translations(&self) -> &Translations {
+        &self.translations
+    }
+
+    pub fn translations_mut(&mut self) -> &mut Translations {
+        &mut self.translations
+    }
+}
+
+// Prefixes `id` with `module:` unless it already names a namespace.
+pub fn namespaced(module: &str, id: &str) -> String {
+    if id.contains(':') {
+        id.to_string()
+    } else {
+        format!("{module}:{id}")
+    }
+}
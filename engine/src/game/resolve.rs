@@ -0,0 +1,576 @@
+use semver::{Version, VersionReq};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt,
+};
+
+use super::{ModuleReq, PreloadModule};
+
+#[derive(Clone, Debug)]
+pub enum Requirer {
+    Profile,
+    Module { name: String, version: Version },
+    OptionalModule { name: String, version: Version },
+}
+
+impl fmt::Display for Requirer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Profile => write!(f, "profile wants"),
+            Self::Module { name, version } => write!(f, "{name} {version} requires"),
+            Self::OptionalModule { name, version } => {
+                write!(f, "{name} {version} optionally requires")
+            }
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct Conflicting {
+    pub module: (String, Version),
+    pub conflicts_with: ModuleReq,
+    pub other: (String, Version),
+}
+
+#[derive(Debug)]
+pub enum ResolveError {
+    Missing {
+        name: String,
+        required_by: Vec<(Requirer, VersionReq)>,
+    },
+    Conflict {
+        name: String,
+        required_by: Vec<(Requirer, VersionReq)>,
+        available: Vec<(String, Version)>,
+    },
+    // Boxed, as it's much larger than the other variants.
+    Conflicting(Box<Conflicting>),
+    Cycle {
+        path: Vec<(String, Version)>,
+    },
+}
+
+impl fmt::Display for ResolveError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Missing { name, required_by } => {
+                write_requirements(f, name, required_by)?;
+                write!(
+                    f,
+                    ", but no module named or providing `{name}` is in the module pool"
+                )
+            }
+            Self::Conflict {
+                name,
+                required_by,
+                available,
+            } => {
+                write_requirements(f, name, required_by)?;
+
+                let available = available
+                    .iter()
+                    .map(|(name, version)| format!("{name} {version}"))
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                if required_by.len() == 1 {
+                    write!(f, ", but only {available} is available")
+                } else {
+                    write!(f, " (available: {available})")
+                }
+            }
+            Self::Conflicting(conflicting) => {
+                let Conflicting {
+                    module: (name, version),
+                    conflicts_with,
+                    other: (other_name, other_version),
+                } = conflicting.as_ref();
+                write!(
+                    f,
+                    "{name} {version} conflicts with {conflicts_with}, but {other_name} {other_version} is also required"
+                )
+            }
+            Self::Cycle { path } => {
+                let path = path
+                    .iter()
+                    .map(|(name, version)| format!("{name} {version}"))
+                    .collect::<Vec<_>>()
+                    .join(" -> ");
+                write!(f, "dependency cycle: {path}")
+            }
+        }
+    }
+}
+
+fn write_requirements(
+    f: &mut fmt::Formatter<'_>,
+    name: &str,
+    required_by: &[(Requirer, VersionReq)],
+) -> fmt::Result {
+    for (i, (requirer, version_req)) in required_by.iter().enumerate() {
+        if i > 0 {
+            write!(f, ", but ")?;
+        }
+        write!(f, "{requirer} {name} {version_req}")?;
+    }
+
+    Ok(())
+}
+
+impl std::error::Error for ResolveError {}
+
+#[derive(Clone)]
+struct Constraint {
+    req: ModuleReq,
+    required_by: Requirer,
+}
+
+#[derive(Clone)]
+struct State {
+    // Module and capability names mapped to the module chosen for them. A
+    // module that provides a required capability appears under both names.
+    selected: BTreeMap<String, usize>,
+    constraints: Vec<Constraint>,
+}
+
+impl State {
+    fn requirements_on(&self, name: &str) -> Vec<(Requirer, VersionReq)> {
+        self.constraints
+            .iter()
+            .filter(|c| c.req.name == name)
+            .map(|c| (c.required_by.clone(), c.req.version_req.clone()))
+            .collect()
+    }
+
+    fn is_selected(&self, idx: usize) -> bool {
+        self.selected.values().any(|&selected| selected == idx)
+    }
+}
+
+fn satisfies(module: &PreloadModule, req: &ModuleReq) -> bool {
+    req.matches(&module.manifest.name, &module.manifest.version)
+        || module
+            .manifest
+            .provides
+            .iter()
+            .any(|capability| req.matches(&capability.name, &capability.version))
+}
+
+fn describe(module: &PreloadModule) -> (String, Version) {
+    (
+        module.manifest.name.clone(),
+        module.manifest.version.clone(),
+    )
+}
+
+// Picks one version of every module reachable from `reqs`, preferring the
+// `locked` version of each, then the highest, and backtracking when a later
+// constraint can't be met. Returns the chosen pool index for every module and
+// capability name.
+pub fn resolve(
+    reqs: &[ModuleReq],
+    pool: &[PreloadModule],
+    locked: &BTreeMap<String, Version>,
+) -> Result<BTreeMap<String, usize>, ResolveError> {
+    let state = State {
+        selected: BTreeMap::new(),
+        constraints: reqs
+            .iter()
+            .map(|req| Constraint {
+                req: req.clone(),
+                required_by: Requirer::Profile,
+            })
+            .collect(),
+    };
+
+    let state = solve(pool, locked, state, 0)?;
+
+    Ok(state.selected)
+}
+
+fn solve(
+    pool: &[PreloadModule],
+    locked: &BTreeMap<String, Version>,
+    state: State,
+    cursor: usize,
+) -> Result<State, ResolveError> {
+    let Some(constraint) = state.constraints.get(cursor) else {
+        check_optional_dependencies(pool, &state)?;
+        return Ok(state);
+    };
+    let name = &constraint.req.name;
+
+    if let Some(&idx) = state.selected.get(name) {
+        return if satisfies(&pool[idx], &constraint.req) {
+            solve(pool, locked, state, cursor + 1)
+        } else {
+            Err(unsatisfied(pool, state.requirements_on(name), name))
+        };
+    }
+
+    let mut candidates = pool
+        .iter()
+        .enumerate()
+        .filter(|(_, module)| {
+            state
+                .constraints
+                .iter()
+                .filter(|c| &c.req.name == name)
+                .all(|c| satisfies(module, &c.req))
+        })
+        .filter(|(idx, module)| {
+            state
+                .selected
+                .get(&module.manifest.name)
+                .is_none_or(|selected| selected == idx)
+        })
+        .collect::<Vec<_>>();
+    // Reuse a module that's already selected before pulling in another
+    // provider, otherwise go for the locked version, then the highest one.
+    let is_locked = |module: &PreloadModule| {
+        locked.get(&module.manifest.name) == Some(&module.manifest.version)
+    };
+    candidates.sort_by(|(a_idx, a), (b_idx, b)| {
+        state
+            .is_selected(*b_idx)
+            .cmp(&state.is_selected(*a_idx))
+            .then_with(|| is_locked(b).cmp(&is_locked(a)))
+            .then_with(|| b.manifest.version.cmp(&a.manifest.version))
+    });
+
+    let mut first_error = None;
+
+    for (idx, module) in candidates {
+        let mut next = state.clone();
+        next.selected.insert(name.clone(), idx);
+
+        if !state.is_selected(idx) {
+            if let Some(e) = find_conflict(pool, &state, idx) {
+                first_error.get_or_insert(e);
+                continue;
+            }
+
+            next.selected.insert(module.manifest.name.clone(), idx);
+            next.constraints
+                .extend(module.manifest.dependencies.iter().map(|req| Constraint {
+                    req: req.clone(),
+                    required_by: Requirer::Module {
+                        name: module.manifest.name.clone(),
+                        version: module.manifest.version.clone(),
+                    },
+                }));
+        }
+
+        match solve(pool, locked, next, cursor + 1) {
+            Ok(resolved) => return Ok(resolved),
+            Err(e) => {
+                first_error.get_or_insert(e);
+            }
+        }
+    }
+
+    Err(first_error.unwrap_or_else(|| unsatisfied(pool, state.requirements_on(name), name)))
+}
+
+fn find_conflict(pool: &[PreloadModule], state: &State, idx: usize) -> Option<ResolveError> {
+    let module = &pool[idx];
+
+    state.selected.values().find_map(|&other_idx| {
+        let other = &pool[other_idx];
+
+        if let Some(req) = module
+            .manifest
+            .conflicts
+            .iter()
+            .find(|req| satisfies(other, req))
+        {
+            return Some(ResolveError::Conflicting(Box::new(Conflicting {
+                module: describe(module),
+                conflicts_with: req.clone(),
+                other: describe(other),
+            })));
+        }
+
+        other
+            .manifest
+            .conflicts
+            .iter()
+            .find(|req| satisfies(module, req))
+            .map(|req| {
+                ResolveError::Conflicting(Box::new(Conflicting {
+                    module: describe(other),
+                    conflicts_with: req.clone(),
+                    other: describe(module),
+                }))
+            })
+    })
+}
+
+// Optional dependencies don't pull modules in, but if the module is selected
+// anyway it still has to be a compatible version.
+fn check_optional_dependencies(pool: &[PreloadModule], state: &State) -> Result<(), ResolveError> {
+    for &idx in state.selected.values() {
+        let module = &pool[idx];
+
+        for req in &module.manifest.optional_dependencies {
+            let Some(&selected) = state.selected.get(&req.name) else {
+                continue;
+            };
+
+            if !satisfies(&pool[selected], req) {
+                let (name, version) = describe(module);
+                let mut required_by = state.requirements_on(&req.name);
+                required_by.push((
+                    Requirer::OptionalModule { name, version },
+                    req.version_req.clone(),
+                ));
+
+                return Err(unsatisfied(pool, required_by, &req.name));
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn unsatisfied(
+    pool: &[PreloadModule],
+    required_by: Vec<(Requirer, VersionReq)>,
+    name: &str,
+) -> ResolveError {
+    let mut available = pool
+        .iter()
+        .filter(|module| {
+            module.manifest.name == name
+                || module
+                    .manifest
+                    .provides
+                    .iter()
+                    .any(|capability| capability.name == name)
+        })
+        .map(describe)
+        .collect::<Vec<_>>();
+    available.sort();
+
+    if available.is_empty() {
+        ResolveError::Missing {
+            name: name.to_string(),
+            required_by,
+        }
+    } else {
+        ResolveError::Conflict {
+            name: name.to_string(),
+            required_by,
+            available,
+        }
+    }
+}
+
+// Orders `selected` so that every module comes after its dependencies. When
+// several modules are ready at once the one with the lowest name goes first,
+// so the order only depends on the resolved set and not on the pool layout.
+// Optional dependencies only affect the order if they were selected.
+pub fn load_order(
+    selected: &BTreeMap<String, usize>,
+    pool: &[PreloadModule],
+) -> Result<Vec<usize>, ResolveError> {
+    let by_name = selected
+        .values()
+        .map(|&idx| (pool[idx].manifest.name.as_str(), idx))
+        .collect::<BTreeMap<_, _>>();
+
+    let dependencies_of = |idx: usize| {
+        let manifest = &pool[idx].manifest;
+
+        manifest
+            .dependencies
+            .iter()
+            .chain(&manifest.optional_dependencies)
+            .filter_map(|req| selected.get(&req.name).copied())
+            .collect::<BTreeSet<_>>()
+    };
+
+    let mut remaining = by_name
+        .values()
+        .map(|&idx| (pool[idx].manifest.name.as_str(), dependencies_of(idx)))
+        .collect::<BTreeMap<_, _>>();
+    let mut order = Vec::with_capacity(remaining.len());
+
+    while !remaining.is_empty() {
+        let ready = remaining
+            .iter()
+            .find(|(_, deps)| deps.is_empty())
+            .map(|(&name, _)| name);
+
+        let Some(name) = ready else {
+            return Err(ResolveError::Cycle {
+                path: find_cycle(&remaining, &by_name, pool),
+            });
+        };
+
+        let idx = by_name[name];
+        remaining.remove(name);
+        for deps in remaining.values_mut() {
+            deps.remove(&idx);
+        }
+        order.push(idx);
+    }
+
+    Ok(order)
+}
+
+fn find_cycle(
+    remaining: &BTreeMap<&str, BTreeSet<usize>>,
+    by_name: &BTreeMap<&str, usize>,
+    pool: &[PreloadModule],
+) -> Vec<(String, Version)> {
+    // Every remaining module still waits on another remaining one, so walking
+    // along dependencies must eventually revisit a module.
+    let mut path = vec![by_name[remaining.keys().next().unwrap()]];
+
+    loop {
+        let current = &pool[*path.last().unwrap()];
+        let next = *remaining[current.manifest.name.as_str()]
+            .iter()
+            .next()
+            .unwrap();
+
+        if let Some(start) = path.iter().position(|&idx| idx == next) {
+            path.drain(..start);
+            path.push(next);
+
+            return path.into_iter().map(|idx| describe(&pool[idx])).collect();
+        }
+
+        path.push(next);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::*;
+    use crate::game::{Manifest, ModuleSource};
+
+    fn req(s: &str) -> ModuleReq {
+        ModuleReq::parse(s.to_string()).unwrap()
+    }
+
+    fn module(name: &str, version: &str, dependencies: &[&str]) -> PreloadModule {
+        PreloadModule {
+            manifest: Manifest {
+                name: name.to_string(),
+                version: Version::parse(version).unwrap(),
+                dependencies: dependencies.iter().map(|s| req(s)).collect(),
+                optional_dependencies: Vec::new(),
+                conflicts: Vec::new(),
+                provides: Vec::new(),
+                authors: Vec::new(),
+                description: None,
+                entrypoint: PathBuf::from("main.wren"),
+                engine: VersionReq::STAR,
+                settings: None,
+                permissions: Vec::new(),
+            },
+            source: ModuleSource::Dir(PathBuf::from(name)),
+        }
+    }
+
+    fn resolve_error(reqs: &[&str], pool: &[PreloadModule]) -> String {
+        let reqs = reqs.iter().map(|s| req(s)).collect::<Vec<_>>();
+        match resolve(&reqs, pool, &BTreeMap::new()) {
+            Ok(_) => panic!("resolved {reqs:?}"),
+            Err(e) => e.to_string(),
+        }
+    }
+
+    #[test]
+    fn explains_missing_modules() {
+        let pool = [module("core", "0.1.0", &["ghost@^1"])];
+
+        assert_eq!(
+            resolve_error(&["core"], &pool),
+            "core 0.1.0 requires ghost ^1, but no module named or providing `ghost` is in the module pool"
+        );
+    }
+
+    #[test]
+    fn explains_unavailable_versions() {
+        let pool = [module("core", "0.1.0", &[])];
+
+        assert_eq!(
+            resolve_error(&["core@^0.2"], &pool),
+            "profile wants core ^0.2, but only core 0.1.0 is available"
+        );
+    }
+
+    #[test]
+    fn explains_conflicting_requirements() {
+        let pool = [
+            module("core", "0.1.0", &[]),
+            module("core", "0.2.0", &[]),
+            module("ui", "1.0.0", &["core@^0.2"]),
+        ];
+
+        assert_eq!(
+            resolve_error(&["core@^0.1", "ui"], &pool),
+            "profile wants core ^0.1, but ui 1.0.0 requires core ^0.2 \
+             (available: core 0.1.0, core 0.2.0)"
+        );
+    }
+
+    #[test]
+    fn explains_declared_conflicts() {
+        let mut a = module("a", "1.0.0", &[]);
+        a.manifest.conflicts.push(req("b"));
+        let pool = [a, module("b", "2.0.0", &[])];
+
+        assert_eq!(
+            resolve_error(&["a", "b"], &pool),
+            "a 1.0.0 conflicts with b *, but b 2.0.0 is also required"
+        );
+    }
+
+    fn selected(pool: &[PreloadModule]) -> BTreeMap<String, usize> {
+        pool.iter()
+            .enumerate()
+            .map(|(idx, module)| (module.manifest.name.clone(), idx))
+            .collect()
+    }
+
+    #[test]
+    fn loads_dependencies_first() {
+        let pool = [
+            module("ui", "1.0.0", &["core"]),
+            module("addon", "1.0.0", &["ui", "core"]),
+            module("core", "1.0.0", &[]),
+            module("base", "1.0.0", &[]),
+        ];
+
+        let order = load_order(&selected(&pool), &pool)
+            .unwrap()
+            .into_iter()
+            .map(|idx| pool[idx].manifest.name.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(order, ["base", "core", "ui", "addon"]);
+    }
+
+    #[test]
+    fn reports_only_the_cycle() {
+        // `app` waits on the cycle without being part of it.
+        let pool = [
+            module("app", "1.0.0", &["x"]),
+            module("x", "1.0.0", &["y"]),
+            module("y", "2.0.0", &["x"]),
+        ];
+
+        match load_order(&selected(&pool), &pool) {
+            Err(e @ ResolveError::Cycle { .. }) => assert_eq!(
+                e.to_string(),
+                "dependency cycle: x 1.0.0 -> y 2.0.0 -> x 1.0.0"
+            ),
+            Err(e) => panic!("unexpected error: {e}"),
+            Ok(order) => panic!("ordered a cycle: {order:?}"),
+        }
+    }
+}
@@ -0,0 +1,461 @@
+use ruwren::{FunctionSignature, SlotId, VMConfig, VMError, VM};
+use std::{
+    cell::RefCell,
+    collections::BTreeMap,
+    fmt, io,
+    ops::{Deref, DerefMut},
+    path::PathBuf,
+    sync::{Arc, Mutex, MutexGuard},
+    time::Duration,
+};
+
+use super::{
+    api::{self, ApiState, LoadingModule, ENGINE_MODULE, ENGINE_MODULE_SOURCE},
+    manifest::Manifest,
+    profile::Profile,
+    registry::Registry,
+    settings::Settings,
+    source::ModuleSource,
+    watchdog::{ScriptBudget, Worker},
+};
+
+#[derive(Debug)]
+pub enum ScriptErrorKind {
+    Io(io::Error),
+    Compile {
+        line: i32,
+        message: String,
+    },
+    Runtime {
+        message: String,
+        // (wren module, line, function), innermost call first
+        frames: Vec<(String, i32, String)>,
+    },
+    // The call was left running on its worker thread.
+    OverBudget {
+        // What ran over, e.g. "handler for `tick`".
+        call: String,
+        budget: Duration,
+    },
+}
+
+#[derive(Debug)]
+pub struct ScriptError {
+    pub module: String,
+    pub file: PathBuf,
+    pub kind: ScriptErrorKind,
+}
+
+impl fmt::Display for ScriptError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let file = self.file.display();
+
+        match &self.kind {
+            ScriptErrorKind::Io(e) => write!(f, "module {}: {file}: {e}", self.module),
+            ScriptErrorKind::Compile { line, message } => {
+                write!(f, "module {}: {file}:{line}: {message}", self.module)
+            }
+            ScriptErrorKind::Runtime { message, frames } => {
+                let line = frames.first().map_or(0, |(_, line, _)| *line);
+                write!(f, "module {}: {file}:{line}: {message}", self.module)?;
+                for (module, line, function) in frames {
+                    write!(f, "\n    at {function} ({module}:{line})")?;
+                }
+                Ok(())
+            }
+            ScriptErrorKind::OverBudget { call, budget } => write!(
+                f,
+                "module {}: {file}: {call} didn't return within its budget of {budget:?}",
+                self.module
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ScriptError {}
+
+impl From<VMError> for ScriptErrorKind {
+    fn from(e: VMError) -> Self {
+        match e {
+            VMError::Compile {
+                line,
+                error: message,
+                ..
+            } => Self::Compile { line, message },
+            VMError::Runtime {
+                error: message,
+                frames,
+            } => Self::Runtime {
+                message,
+                frames: frames
+                    .into_iter()
+                    .map(|frame| (frame.module, frame.line, frame.function))
+                    .collect(),
+            },
+        }
+    }
+}
+
+// A Wren VM for every loaded module, so scripts can't import each other and
+// reach another module's variables, like its `Files` handle. Each module's
+// entrypoint is interpreted as a Wren module named after the engine module.
+// Scripts reach the engine through the `engine` Wren module (see
+// `engine.wren`), which every VM gets its own copy of. Every VM runs on a
+// worker thread of its own, so a call that runs over its budget can be left
+// behind.
+pub struct ScriptHost {
+    // module -> the worker running its VM
+    workers: RefCell<BTreeMap<String, Worker>>,
+    state: Arc<Mutex<ApiState>>,
+
+    budget: ScriptBudget,
+}
+
+// The registry of a `ScriptHost`, which scripts can't use while this is held.
+pub struct RegistryGuard<'a>(MutexGuard<'a, ApiState>);
+
+impl Deref for RegistryGuard<'_> {
+    type Target = Registry;
+
+    fn deref(&self) -> &Registry {
+        &self.0.registry
+    }
+}
+
+impl DerefMut for RegistryGuard<'_> {
+    fn deref_mut(&mut self) -> &mut Registry {
+        &mut self.0.registry
+    }
+}
+
+impl ScriptHost {
+    pub fn new(profile: &Profile, locale: &str) -> Self {
+        let state = Arc::new(Mutex::new(ApiState {
+            module_data_dir: profile.module_data_dir(),
+            locale: locale.to_string(),
+            ..Default::default()
+        }));
+
+        Self {
+            workers: RefCell::default(),
+            state,
+            budget: profile.script_budget,
+        }
+    }
+
+    pub fn registry(&self) -> RegistryGuard<'_> {
+        RegistryGuard(self.state.lock().unwrap())
+    }
+
+    pub fn run_entrypoint(
+        &self,
+        manifest: &Manifest,
+        source: &ModuleSource,
+        settings: Settings,
+    ) -> Result<(), ScriptError> {
+        let module = manifest.name.clone();
+        let entrypoint = manifest.entrypoint.as_path();
+        let file = source.display_path(entrypoint);
+        let error = |kind| ScriptError {
+            module: module.clone(),
+            file: file.clone(),
+            kind,
+        };
+
+        let code = source
+            .read_to_string(entrypoint)
+            .map_err(|e| error(ScriptErrorKind::Io(e)))?;
+
+        {
+            let mut state = self.state.lock().unwrap();
+            state.loading = Some(LoadingModule {
+                name: module.clone(),
+                source: source.clone(),
+                settings,
+                permissions: manifest.permissions.clone(),
+            });
+            state.modules.insert(module.clone(), file.clone());
+        }
+
+        let worker = Worker::spawn(format!("module {module}"), {
+            let state = self.state.clone();
+            let module = module.clone();
+            move || {
+                api::install(state, module);
+                let vm = VMConfig::new().library(&api::library()).build();
+                vm.interpret(ENGINE_MODULE, ENGINE_MODULE_SOURCE)
+                    .unwrap_or_else(|_| {
+                        panic!("failed to load the `{ENGINE_MODULE}` script module")
+                    });
+                vm
+            }
+        });
+
+        let result = worker.run(self.budget.load, {
+            let module = module.clone();
+            move |vm| vm.interpret(module, code).map_err(ScriptErrorKind::from)
+        });
+        self.state.lock().unwrap().loading = None;
+
+        // Modules can depend on what an entrypoint registers, so one that runs
+        // over fails the load instead of only disabling the module.
+        let Some(result) = result else {
+            self.disable(&module);
+            return Err(error(ScriptErrorKind::OverBudget {
+                call: "entrypoint".to_string(),
+                budget: self.budget.load,
+            }));
+        };
+        self.workers.borrow_mut().insert(module.clone(), worker);
+
+        result.map_err(error)
+    }
+
+    // Calls every handler of `event`, in the order they were registered.
+    pub fn dispatch_event(&self, event: &str, args: &[&str]) -> Result<(), ScriptError> {
+        let handlers = self
+            .state
+            .lock()
+            .unwrap()
+            .handlers
+            .get(event)
+            .cloned()
+            .unwrap_or_default();
+
+        for (pos, module) in handlers.iter().enumerate() {
+            // `Events` keeps the handlers of each module in its own VM.
+            let idx = handlers[..pos].iter().filter(|m| *m == module).count();
+            let event = event.to_string();
+            let args = owned(args);
+            self.call_handler(
+                module,
+                format!("handler for `{event}`"),
+                "Events",
+                "call_",
+                3,
+                move |vm| {
+                    vm.set_slot_string(1, &event);
+                    vm.set_slot_double(2, idx as f64);
+                    set_list(vm, 3, 4, &args);
+                },
+            )?;
+        }
+
+        Ok(())
+    }
+
+    pub fn run_command(&self, name: &str, args: &[&str]) -> Result<(), ScriptError> {
+        let module = self.registry().commands().get(name).cloned();
+        let Some(module) = module else {
+            return Err(ScriptError {
+                module: ENGINE_MODULE.to_string(),
+                file: PathBuf::from("engine.wren"),
+                kind: ScriptErrorKind::Runtime {
+                    message: format!("unknown command `{name}`"),
+                    frames: Vec::new(),
+                },
+            });
+        };
+
+        let name = name.to_string();
+        let args = owned(args);
+        self.call_handler(
+            &module,
+            format!("command `{name}`"),
+            "Commands",
+            "run_",
+            2,
+            move |vm| {
+                vm.set_slot_string(1, &name);
+                set_list(vm, 2, 3, &args);
+            },
+        )
+    }
+
+    // Runs a handler registered by `module` within the handler budget. A
+    // module whose handler runs over is disabled.
+    fn call_handler(
+        &self,
+        module: &str,
+        call: String,
+        class: &'static str,
+        method: &'static str,
+        arity: usize,
+        set_args: impl FnOnce(&VM) + Send + 'static,
+    ) -> Result<(), ScriptError> {
+        if self.state.lock().unwrap().disabled.contains(module) {
+            return Ok(());
+        }
+
+        if let Some(result) = self.call_engine(module, class, method, arity, set_args) {
+            return result;
+        }
+
+        eprintln!("warning: disabling module {module}");
+        self.disable(module);
+
+        let file = self.state.lock().unwrap().modules[module].clone();
+        Err(ScriptError {
+            module: module.to_string(),
+            file,
+            kind: ScriptErrorKind::OverBudget {
+                call,
+                budget: self.budget.handler,
+            },
+        })
+    }
+
+    // Stops calling into `module` after one of its calls ran over. The call
+    // keeps running on the module's worker thread, and is aborted if it calls
+    // into the engine again, see `api::out_of_time`.
+    fn disable(&self, module: &str) {
+        let mut state = self.state.lock().unwrap();
+        state.disabled.insert(module.to_string());
+        for modules in state.handlers.values_mut() {
+            modules.retain(|m| m != module);
+        }
+        state.registry.unregister_commands(module);
+        drop(state);
+
+        self.workers.borrow_mut().remove(module);
+    }
+
+    // Calls `class.method(...)` on a class from the `engine` Wren module in the
+    // VM of `module`, with `set_args` filling in the `arity` arguments from
+    // slot 1 up. `None` if the call didn't return within the handler budget.
+    fn call_engine(
+        &self,
+        module: &str,
+        class: &'static str,
+        method: &'static str,
+        arity: usize,
+        set_args: impl FnOnce(&VM) + Send + 'static,
+    ) -> Option<Result<(), ScriptError>> {
+        let result = self.workers.borrow()[module].run(self.budget.handler, move |vm| {
+            vm.execute(|vm| {
+                // One spare slot for building lists.
+                vm.ensure_slots(arity + 2);
+                vm.get_variable(ENGINE_MODULE, class, 0);
+                set_args(vm);
+            });
+
+            vm.call(FunctionSignature::new_function(method, arity))
+                .map_err(ScriptErrorKind::from)
+        })?;
+
+        Some(result.map_err(|kind| {
+            // Blame the innermost engine module on the stack, if any.
+            let state = self.state.lock().unwrap();
+            let culprit = match &kind {
+                ScriptErrorKind::Runtime { frames, .. } => frames
+                    .iter()
+                    .find_map(|(module, _, _)| state.modules.get_key_value(module)),
+                _ => None,
+            };
+            let (module, file) = match culprit {
+                Some((module, file)) => (module.clone(), file.clone()),
+                None => (ENGINE_MODULE.to_string(), PathBuf::from("engine.wren")),
+            };
+
+            ScriptError { module, file, kind }
+        }))
+    }
+}
+
+fn owned(args: &[&str]) -> Vec<String> {
+    args.iter().map(|arg| arg.to_string()).collect()
+}
+
+// Fills `slot` with a new list of `args`, using `scratch` for the elements.
+fn set_list(vm: &VM, slot: SlotId, scratch: SlotId, args: &[String]) {
+    vm.set_slot_new_list(slot);
+    for (i, arg) in args.iter().enumerate() {
+        vm.set_slot_string(scratch, arg);
+        vm.insert_in_list(slot, i as i32, scratch);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::testing::TempDir;
+    use std::time::Instant;
+
+    // A host with tight budgets and a module `slow` whose entrypoint is
+    // `main`.
+    fn host(dir: &TempDir, main: &str) -> (ScriptHost, Manifest, ModuleSource) {
+        dir.write(
+            "profile/profile.yml",
+            "name: test\nmodules: []\nscript_budget:\n  load_ms: 200\n  handler_ms: 50\n",
+        );
+        dir.write("slow/module.yml", "name: slow\nversion: 0.1.0\n");
+        dir.write("slow/main.wren", main);
+
+        let profile = Profile::load(dir.path().join("profile")).unwrap();
+        let source = ModuleSource::Dir(dir.path().join("slow"));
+        let manifest = Manifest::load(&source).unwrap();
+
+        (ScriptHost::new(&profile, "en"), manifest, source)
+    }
+
+    #[test]
+    fn entrypoint_stuck_in_a_loop_fails_the_load() {
+        let dir = TempDir::new();
+        let (host, manifest, source) = host(&dir, "while (true) {}\n");
+
+        let started = Instant::now();
+        let err = host
+            .run_entrypoint(&manifest, &source, Settings::new())
+            .unwrap_err();
+        assert!(
+            matches!(err.kind, ScriptErrorKind::OverBudget { .. }),
+            "{err}"
+        );
+        assert!(started.elapsed() < Duration::from_secs(2));
+    }
+
+    #[test]
+    fn handler_stuck_in_a_loop_disables_its_module() {
+        let dir = TempDir::new();
+        let (host, manifest, source) = host(
+            &dir,
+            "import \"engine\" for Events, Commands\n\
+             Events.on(\"tick\") {|args| while (true) {} }\n\
+             Commands.register(\"hello\") {|args| }\n",
+        );
+        host.run_entrypoint(&manifest, &source, Settings::new())
+            .unwrap();
+
+        let err = host.dispatch_event("tick", &[]).unwrap_err();
+        assert!(
+            matches!(err.kind, ScriptErrorKind::OverBudget { .. }),
+            "{err}"
+        );
+        assert_eq!(err.module, "slow");
+
+        // Its handlers and commands are gone.
+        host.dispatch_event("tick", &[]).unwrap();
+        let err = host.run_command("hello", &[]).unwrap_err();
+        assert!(err.to_string().contains("unknown command `hello`"), "{err}");
+    }
+
+    #[test]
+    fn handler_errors_are_returned() {
+        let dir = TempDir::new();
+        let (host, manifest, source) = host(
+            &dir,
+            "import \"engine\" for Events\n\
+             Events.on(\"tick\") {|args| Fiber.abort(\"boom\") }\n",
+        );
+        host.run_entrypoint(&manifest, &source, Settings::new())
+            .unwrap();
+
+        for _ in 0..2 {
+            let err = host.dispatch_event("tick", &[]).unwrap_err();
+            assert!(
+                matches!(&err.kind, ScriptErrorKind::Runtime { message, .. } if message == "boom"),
+                "{err}"
+            );
+        }
+    }
+}
@@ -0,0 +1,537 @@
+use std::{collections::BTreeMap, fmt};
+use yaml_rust::Yaml;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SettingType {
+    String,
+    Integer,
+    Float,
+    Boolean,
+}
+
+impl SettingType {
+    fn parse(s: &str) -> Option<Self> {
+        match s {
+            "string" => Some(Self::String),
+            "integer" => Some(Self::Integer),
+            "float" => Some(Self::Float),
+            "boolean" => Some(Self::Boolean),
+            _ => None,
+        }
+    }
+
+    // For error messages, e.g. "an integer".
+    fn with_article(self) -> String {
+        match self {
+            Self::Integer => format!("an {self}"),
+            _ => format!("a {self}"),
+        }
+    }
+}
+
+impl fmt::Display for SettingType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::String => write!(f, "string"),
+            Self::Integer => write!(f, "integer"),
+            Self::Float => write!(f, "float"),
+            Self::Boolean => write!(f, "boolean"),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum SettingValue {
+    String(String),
+    Integer(i64),
+    Float(f64),
+    Boolean(bool),
+}
+
+impl SettingValue {
+    fn from_yaml(yaml: &Yaml) -> Option<Self> {
+        match yaml {
+            Yaml::String(s) => Some(Self::String(s.clone())),
+            Yaml::Integer(i) => Some(Self::Integer(*i)),
+            Yaml::Real(_) => yaml.as_f64().map(Self::Float),
+            Yaml::Boolean(b) => Some(Self::Boolean(*b)),
+            _ => None,
+        }
+    }
+
+    // Converts to `ty` where that doesn't lose anything, i.e. integers can be
+    // used for float options.
+    fn coerce(self, ty: SettingType) -> Option<Self> {
+        match (self, ty) {
+            (value @ Self::String(_), SettingType::String)
+            | (value @ Self::Integer(_), SettingType::Integer)
+            | (value @ Self::Float(_), SettingType::Float)
+            | (value @ Self::Boolean(_), SettingType::Boolean) => Some(value),
+            (Self::Integer(i), SettingType::Float) => Some(Self::Float(i as f64)),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for SettingValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::String(s) => write!(f, "{s:?}"),
+            Self::Integer(i) => write!(f, "{i}"),
+            Self::Float(x) => write!(f, "{x}"),
+            Self::Boolean(b) => write!(f, "{b}"),
+        }
+    }
+}
+
+const DECLARATION_KEYS: &[&str] = &["type", "default", "min", "max", "values", "description"];
+
+// How one option is declared under `settings` in `module.yml`.
+#[derive(Clone, Debug)]
+pub struct SettingSchema {
+    pub ty: SettingType,
+    pub default: Option<SettingValue>,
+    pub min: Option<f64>,
+    pub max: Option<f64>,
+    // If not empty, the only values the option can have.
+    pub values: Vec<SettingValue>,
+    pub description: Option<String>,
+}
+
+impl SettingSchema {
+    fn check(&self, value: SettingValue) -> Result<SettingValue, SettingsErrorKind> {
+        let value = value
+            .coerce(self.ty)
+            .ok_or_else(|| SettingsErrorKind::WrongType {
+                expected: self.ty.with_article(),
+            })?;
+
+        let number = match value {
+            SettingValue::Integer(i) => Some(i as f64),
+            SettingValue::Float(x) => Some(x),
+            _ => None,
+        };
+        if let Some(number) = number {
+            if self.min.is_some_and(|min| number < min) || self.max.is_some_and(|max| number > max)
+            {
+                return Err(SettingsErrorKind::OutOfRange {
+                    min: self.min,
+                    max: self.max,
+                });
+            }
+        }
+
+        if !self.values.is_empty() && !self.values.contains(&value) {
+            return Err(SettingsErrorKind::NotAllowed {
+                values: self.values.clone(),
+            });
+        }
+
+        Ok(value)
+    }
+
+    // Describes `min`, `max` and `values` for the settings dump.
+    pub fn allowed(&self) -> String {
+        if !self.values.is_empty() {
+            return join(&self.values);
+        }
+
+        match (self.min, self.max) {
+            (Some(min), Some(max)) => format!("{min} to {max}"),
+            (Some(min), None) => format!("at least {min}"),
+            (None, Some(max)) => format!("at most {max}"),
+            (None, None) => String::new(),
+        }
+    }
+}
+
+// The options a module accepts, as declared under `settings` in `module.yml`.
+pub type SettingsSchema = BTreeMap<String, SettingSchema>;
+
+pub type Settings = BTreeMap<String, SettingValue>;
+
+fn join(values: &[SettingValue]) -> String {
+    values
+        .iter()
+        .map(SettingValue::to_string)
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+pub fn parse_schema(yaml: &Yaml) -> Result<SettingsSchema, String> {
+    let Yaml::Hash(hash) = yaml else {
+        return Err("expected a map of option names to option declarations".to_string());
+    };
+
+    hash.iter()
+        .map(|(name, declaration)| {
+            let Yaml::String(name) = name else {
+                return Err("option names must be strings".to_string());
+            };
+            let Yaml::Hash(keys) = declaration else {
+                return Err(format!("`{name}`: expected a map"));
+            };
+            for key in keys.keys() {
+                match key.as_str() {
+                    Some(key) if DECLARATION_KEYS.contains(&key) => {}
+                    _ => return Err(format!("`{name}`: unknown key {key:?}")),
+                }
+            }
+
+            let ty = match &declaration["type"] {
+                Yaml::String(ty) => SettingType::parse(ty).ok_or_else(|| {
+                    format!(
+                        "`{name}`: unknown type `{ty}` (expected string, integer, float or boolean)"
+                    )
+                })?,
+                _ => return Err(format!("`{name}`: `type` is required")),
+            };
+
+            let number = |key: &str| match &declaration[key] {
+                Yaml::BadValue => Ok(None),
+                _ if !matches!(ty, SettingType::Integer | SettingType::Float) => Err(format!(
+                    "`{name}`: `{key}` can only be used with integer and float options"
+                )),
+                Yaml::Integer(i) => Ok(Some(*i as f64)),
+                value @ Yaml::Real(_) => Ok(value.as_f64()),
+                _ => Err(format!("`{name}`: `{key}` must be a number")),
+            };
+            let min = number("min")?;
+            let max = number("max")?;
+
+            let values = match &declaration["values"] {
+                Yaml::Array(values) => values
+                    .iter()
+                    .map(|value| {
+                        SettingValue::from_yaml(value)
+                            .and_then(|value| value.coerce(ty))
+                            .ok_or_else(|| format!("`{name}`: `values` must all be {ty}s"))
+                    })
+                    .collect::<Result<Vec<_>, _>>()?,
+                Yaml::BadValue => vec![],
+                _ => return Err(format!("`{name}`: `values` must be a list")),
+            };
+
+            let description = match &declaration["description"] {
+                Yaml::String(s) => Some(s.clone()),
+                Yaml::BadValue => None,
+                _ => return Err(format!("`{name}`: `description` must be a string")),
+            };
+
+            let mut schema = SettingSchema {
+                ty,
+                default: None,
+                min,
+                max,
+                values,
+                description,
+            };
+
+            schema.default = match &declaration["default"] {
+                Yaml::BadValue => None,
+                default => {
+                    let default = SettingValue::from_yaml(default).ok_or_else(|| {
+                        format!("`{name}`: `default` must be {}", ty.with_article())
+                    })?;
+                    Some(
+                        schema
+                            .check(default)
+                            .map_err(|e| format!("`{name}`: invalid default: {e}"))?,
+                    )
+                }
+            };
+
+            Ok((name.clone(), schema))
+        })
+        .collect()
+}
+
+#[derive(Debug)]
+pub enum SettingsErrorKind {
+    NotAMap,
+    UnknownOption,
+    WrongType { expected: String },
+    OutOfRange { min: Option<f64>, max: Option<f64> },
+    NotAllowed { values: Vec<SettingValue> },
+}
+
+impl fmt::Display for SettingsErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::NotAMap => write!(f, "expected a map of options"),
+            Self::UnknownOption => write!(f, "not an option of this module"),
+            Self::WrongType { expected } => write!(f, "expected {expected}"),
+            Self::OutOfRange { min, max } => match (min, max) {
+                (Some(min), Some(max)) => write!(f, "must be between {min} and {max}"),
+                (Some(min), None) => write!(f, "must be at least {min}"),
+                (_, Some(max)) => write!(f, "must be at most {max}"),
+                (None, None) => write!(f, "out of range"),
+            },
+            Self::NotAllowed { values } => write!(f, "must be one of {}", join(values)),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct SettingsError {
+    pub module: String,
+    pub option: Option<String>,
+    pub kind: SettingsErrorKind,
+}
+
+impl fmt::Display for SettingsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "settings for {}", self.module)?;
+        if let Some(option) = &self.option {
+            write!(f, ": `{option}`")?;
+        }
+        write!(f, ": {}", self.kind)
+    }
+}
+
+impl std::error::Error for SettingsError {}
+
+// Checks the profile's settings for a module against the module's schema and
+// fills in the defaults of options the profile doesn't set. A module without a
+// schema accepts any option, as long as it's a plain value.
+pub fn validate(
+    module: &str,
+    schema: Option<&SettingsSchema>,
+    values: &Yaml,
+) -> Result<Settings, SettingsError> {
+    let error = |option: Option<&str>, kind| SettingsError {
+        module: module.to_string(),
+        option: option.map(str::to_string),
+        kind,
+    };
+
+    let empty = yaml_rust::yaml::Hash::new();
+    let values = match values {
+        Yaml::Hash(hash) => hash,
+        Yaml::BadValue | Yaml::Null => &empty,
+        _ => return Err(error(None, SettingsErrorKind::NotAMap)),
+    };
+
+    let mut settings = values
+        .iter()
+        .map(|(option, value)| {
+            let Yaml::String(option) = option else {
+                return Err(error(None, SettingsErrorKind::NotAMap));
+            };
+
+            let value = SettingValue::from_yaml(value).ok_or_else(|| {
+                error(
+                    Some(option),
+                    SettingsErrorKind::WrongType {
+                        expected: "a string, number or boolean".to_string(),
+                    },
+                )
+            })?;
+
+            let value = match schema {
+                Some(schema) => schema
+                    .get(option)
+                    .ok_or(SettingsErrorKind::UnknownOption)
+                    .and_then(|declared| declared.check(value))
+                    .map_err(|kind| error(Some(option), kind))?,
+                None => value,
+            };
+
+            Ok((option.clone(), value))
+        })
+        .collect::<Result<Settings, _>>()?;
+
+    for (option, declared) in schema.into_iter().flatten() {
+        if let Some(default) = &declared.default {
+            settings
+                .entry(option.clone())
+                .or_insert_with(|| default.clone());
+        }
+    }
+
+    Ok(settings)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use yaml_rust::YamlLoader;
+
+    fn yaml(s: &str) -> Yaml {
+        YamlLoader::load_from_str(s).unwrap().remove(0)
+    }
+
+    fn schema(s: &str) -> SettingsSchema {
+        parse_schema(&yaml(s)).unwrap()
+    }
+
+    fn validate_err(schema: Option<&SettingsSchema>, values: &str) -> SettingsError {
+        validate("core", schema, &yaml(values)).unwrap_err()
+    }
+
+    #[test]
+    fn settings_without_a_schema() {
+        let settings = validate(
+            "core",
+            None,
+            &yaml("name: Steve\nseed: 42\ngravity: 9.5\nhardcore: true"),
+        )
+        .unwrap();
+        assert_eq!(
+            settings,
+            Settings::from([
+                (
+                    "name".to_string(),
+                    SettingValue::String("Steve".to_string())
+                ),
+                ("seed".to_string(), SettingValue::Integer(42)),
+                ("gravity".to_string(), SettingValue::Float(9.5)),
+                ("hardcore".to_string(), SettingValue::Boolean(true)),
+            ])
+        );
+
+        // A module the profile has no settings for.
+        assert!(validate("core", None, &Yaml::BadValue).unwrap().is_empty());
+        assert!(validate("core", None, &Yaml::Null).unwrap().is_empty());
+
+        let err = validate_err(None, "[1, 2]");
+        assert!(matches!(err.kind, SettingsErrorKind::NotAMap));
+        let err = validate_err(None, "spawn: [0, 64, 0]");
+        assert_eq!(err.option.as_deref(), Some("spawn"));
+        assert!(matches!(err.kind, SettingsErrorKind::WrongType { .. }));
+    }
+
+    #[test]
+    fn settings_are_checked_against_the_schema() {
+        let schema =
+            schema("seed:\n  type: integer\ngravity:\n  type: float\nname:\n  type: string");
+
+        let settings = validate("core", Some(&schema), &yaml("seed: 42\ngravity: 10")).unwrap();
+        assert_eq!(settings["seed"], SettingValue::Integer(42));
+        // Integers are accepted for floats.
+        assert_eq!(settings["gravity"], SettingValue::Float(10.0));
+
+        let err = validate_err(Some(&schema), "sede: 42");
+        assert_eq!(err.option.as_deref(), Some("sede"));
+        assert!(matches!(err.kind, SettingsErrorKind::UnknownOption));
+
+        for values in ["seed: 4.2", "seed: \"42\"", "name: 42", "gravity: true"] {
+            let err = validate_err(Some(&schema), values);
+            assert!(
+                matches!(err.kind, SettingsErrorKind::WrongType { .. }),
+                "{values}: {err}"
+            );
+        }
+        assert_eq!(
+            validate_err(Some(&schema), "seed: 4.2").to_string(),
+            "settings for core: `seed`: expected an integer"
+        );
+    }
+
+    #[test]
+    fn defaults_ranges_and_allowed_values() {
+        let schema = schema(
+            "difficulty:\n  type: string\n  default: normal\n  values: [easy, normal, hard]\n  \
+             description: How hard mobs hit\n\
+             view_distance:\n  type: integer\n  default: 8\n  min: 2\n  max: 32\n\
+             gravity:\n  type: float\n  min: 0\n\
+             seed:\n  type: integer\n",
+        );
+
+        let settings = validate("core", Some(&schema), &yaml("difficulty: hard")).unwrap();
+        assert_eq!(
+            settings,
+            Settings::from([
+                (
+                    "difficulty".to_string(),
+                    SettingValue::String("hard".to_string())
+                ),
+                ("view_distance".to_string(), SettingValue::Integer(8)),
+            ])
+        );
+
+        for values in ["view_distance: 1", "view_distance: 33", "gravity: -0.5"] {
+            let err = validate_err(Some(&schema), values);
+            assert!(
+                matches!(err.kind, SettingsErrorKind::OutOfRange { .. }),
+                "{values}: {err}"
+            );
+        }
+        validate(
+            "core",
+            Some(&schema),
+            &yaml("view_distance: 32\ngravity: 0"),
+        )
+        .unwrap();
+
+        let err = validate_err(Some(&schema), "difficulty: nightmare");
+        assert_eq!(
+            err.to_string(),
+            "settings for core: `difficulty`: must be one of \"easy\", \"normal\", \"hard\""
+        );
+
+        assert_eq!(
+            schema["difficulty"].allowed(),
+            "\"easy\", \"normal\", \"hard\""
+        );
+        assert_eq!(schema["view_distance"].allowed(), "2 to 32");
+        assert_eq!(schema["gravity"].allowed(), "at least 0");
+        assert_eq!(schema["seed"].allowed(), "");
+        assert_eq!(
+            schema["difficulty"].description.as_deref(),
+            Some("How hard mobs hit")
+        );
+    }
+
+    #[test]
+    fn invalid_schemas() {
+        let cases = [
+            ("[seed]", "expected a map of option names"),
+            ("seed: integer", "`seed`: expected a map"),
+            ("seed:\n  default: 1", "`seed`: `type` is required"),
+            ("seed:\n  type: int", "unknown type `int`"),
+            ("seed:\n  type: integer\n  step: 2", "`seed`: unknown key"),
+            (
+                "name:\n  type: string\n  min: 1",
+                "`min` can only be used with integer and float",
+            ),
+            (
+                "seed:\n  type: integer\n  max: lots",
+                "`max` must be a number",
+            ),
+            (
+                "seed:\n  type: integer\n  values: 1",
+                "`values` must be a list",
+            ),
+            (
+                "seed:\n  type: integer\n  values: [1, a]",
+                "`values` must all be integers",
+            ),
+            (
+                "seed:\n  type: integer\n  default: [1]",
+                "`default` must be an integer",
+            ),
+            (
+                "seed:\n  type: integer\n  default: 1.5",
+                "invalid default: expected an integer",
+            ),
+            (
+                "seed:\n  type: integer\n  max: 10\n  default: 11",
+                "invalid default: must be at most 10",
+            ),
+            (
+                "seed:\n  type: integer\n  values: [1, 2]\n  default: 3",
+                "invalid default: must be one of 1, 2",
+            ),
+            (
+                "seed:\n  type: integer\n  description: 1",
+                "`description` must be a string",
+            ),
+        ];
+
+        for (source, expected) in cases {
+            let err = parse_schema(&yaml(source)).unwrap_err();
+            assert!(err.contains(expected), "{source}: {err}");
+        }
+    }
+}
@@ -0,0 +1,301 @@
+use std::{
+    fmt, fs,
+    io::{self, Read},
+    path::{Component, Path, PathBuf},
+};
+use zip::{result::ZipError, ZipArchive};
+
+pub const ARCHIVE_EXTENSION: &str = "yassmod";
+
+// Where a module's files live: either an unpacked directory or a `.yassmod`
+// zip archive with `module.yml` at its root. Archives are read in place.
+#[derive(Clone, Debug)]
+pub enum ModuleSource {
+    Dir(PathBuf),
+    Archive(PathBuf),
+}
+
+impl fmt::Display for ModuleSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Dir(path) | Self::Archive(path) => write!(f, "{}", path.display()),
+        }
+    }
+}
+
+impl ModuleSource {
+    pub fn path(&self) -> &Path {
+        match self {
+            Self::Dir(path) | Self::Archive(path) => path,
+        }
+    }
+
+    // A path to `file` for error messages, e.g. `core-0.1.0.yassmod/main.wren`.
+    pub fn display_path(&self, file: &Path) -> PathBuf {
+        self.path().join(file)
+    }
+
+    pub fn read(&self, file: &Path) -> io::Result<Vec<u8>> {
+        match self {
+            Self::Dir(dir) => fs::read(dir_path(dir, file)?),
+            Self::Archive(path) => {
+                let mut archive = open_archive(path)?;
+                let mut entry = archive.by_name(&archive_name(file)?).map_err(zip_to_io)?;
+
+                let mut bytes = Vec::with_capacity(entry.size() as usize);
+                entry.read_to_end(&mut bytes)?;
+                Ok(bytes)
+            }
+        }
+    }
+
+    pub fn read_to_string(&self, file: &Path) -> io::Result<String> {
+        String::from_utf8(self.read(file)?)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+
+    // Every file below `dir`, relative to the module root, in sorted order.
+    pub fn list(&self, dir: &Path) -> io::Result<Vec<PathBuf>> {
+        let mut files = vec![];
+
+        match self {
+            Self::Dir(root) => {
+                if root.join(dir).is_dir() {
+                    walk(root, &root.join(dir), &mut files)?;
+                }
+            }
+            Self::Archive(path) => {
+                let archive = open_archive(path)?;
+                let prefix = match archive_name(dir)? {
+                    name if name.is_empty() => name,
+                    name => format!("{name}/"),
+                };
+
+                files.extend(
+                    archive
+                        .file_names()
+                        .filter(|name| name.starts_with(&prefix) && !name.ends_with('/'))
+                        .map(PathBuf::from),
+                );
+            }
+        }
+
+        files.sort();
+
+        Ok(files)
+    }
+}
+
+fn walk(root: &Path, dir: &Path, files: &mut Vec<PathBuf>) -> io::Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            walk(root, &path, files)?;
+        } else {
+            files.push(path.strip_prefix(root).unwrap().to_path_buf());
+        }
+    }
+
+    Ok(())
+}
+
+// Resolves symlinks and `..` in `file`, which can't lead out of the module
+// directory.
+fn dir_path(dir: &Path, file: &Path) -> io::Result<PathBuf> {
+    let path = dir.join(file).canonicalize()?;
+    if !path.starts_with(dir.canonicalize()?) {
+        return Err(not_inside(file));
+    }
+
+    Ok(path)
+}
+
+fn open_archive(path: &Path) -> io::Result<ZipArchive<fs::File>> {
+    ZipArchive::new(fs::File::open(path)?).map_err(zip_to_io)
+}
+
+// Zip entry names always use `/`, and can't escape the archive root.
+fn archive_name(file: &Path) -> io::Result<String> {
+    file.components()
+        .map(|c| match c {
+            Component::Normal(s) => Ok(s.to_string_lossy()),
+            _ => Err(not_inside(file)),
+        })
+        .collect::<Result<Vec<_>, _>>()
+        .map(|parts| parts.join("/"))
+}
+
+fn not_inside(file: &Path) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidInput,
+        format!("`{}` is not a path inside the module", file.display()),
+    )
+}
+
+fn zip_to_io(e: ZipError) -> io::Error {
+    match e {
+        ZipError::Io(e) => e,
+        ZipError::FileNotFound => io::Error::new(io::ErrorKind::NotFound, e),
+        e => io::Error::new(io::ErrorKind::InvalidData, e),
+    }
+}
+
+// A file inside a module, e.g. a texture.
+#[derive(Clone, Debug)]
+pub struct Asset {
+    pub source: ModuleSource,
+    pub path: PathBuf,
+}
+
+impl Asset {
+    pub fn read(&self) -> io::Result<Vec<u8>> {
+        self.source.read(&self.path)
+    }
+}
+
+impl fmt::Display for Asset {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.source.display_path(&self.path).display())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::testing::TempDir;
+    use std::io::Write;
+    use zip::{write::FileOptions, ZipWriter};
+
+    const FILES: &[(&str, &str)] = &[
+        ("module.yml", "name: core\nversion: 0.1.0\n"),
+        ("main.wren", "System.print(1)"),
+        ("data/blocks.yml", "stone: {}"),
+        ("data/loot/chests.yml", "{}"),
+        ("assets/textures/stone.png", "png"),
+    ];
+
+    fn archive(dir: &TempDir) -> ModuleSource {
+        let path = dir.path().join(format!("core-0.1.0.{ARCHIVE_EXTENSION}"));
+        let mut zip = ZipWriter::new(fs::File::create(&path).unwrap());
+
+        zip.add_directory("data/", FileOptions::default()).unwrap();
+        for (name, contents) in FILES {
+            zip.start_file(*name, FileOptions::default()).unwrap();
+            zip.write_all(contents.as_bytes()).unwrap();
+        }
+        zip.finish().unwrap();
+
+        ModuleSource::Archive(path)
+    }
+
+    #[test]
+    fn archives_read_like_dirs() {
+        let dir = TempDir::new();
+        for (name, contents) in FILES {
+            dir.write(Path::new("core").join(name), contents);
+        }
+        dir.write("outside.txt", "");
+
+        for source in [archive(&dir), ModuleSource::Dir(dir.path().join("core"))] {
+            for (name, contents) in FILES {
+                assert_eq!(
+                    source.read_to_string(name.as_ref()).unwrap(),
+                    *contents,
+                    "{source}"
+                );
+            }
+
+            let files = |dir: &str| {
+                source
+                    .list(dir.as_ref())
+                    .unwrap()
+                    .into_iter()
+                    .map(|path| path.to_string_lossy().replace('\\', "/"))
+                    .collect::<Vec<_>>()
+            };
+            assert_eq!(
+                files(""),
+                [
+                    "assets/textures/stone.png",
+                    "data/blocks.yml",
+                    "data/loot/chests.yml",
+                    "main.wren",
+                    "module.yml",
+                ]
+            );
+            assert_eq!(files("data"), ["data/blocks.yml", "data/loot/chests.yml"]);
+            assert!(files("patches").is_empty());
+
+            let err = source.read("missing.wren".as_ref()).unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::NotFound, "{source}");
+            let err = source.read("../outside.txt".as_ref()).unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::InvalidInput, "{source}");
+        }
+    }
+
+    #[test]
+    fn broken_archives() {
+        let dir = TempDir::new();
+        let path = dir.write(format!("core.{ARCHIVE_EXTENSION}"), "not a zip");
+
+        let err = ModuleSource::Archive(path)
+            .read("module.yml".as_ref())
+            .unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn archive_names() {
+        assert_eq!(archive_name(Path::new("")).unwrap(), "");
+        assert_eq!(archive_name(Path::new("main.wren")).unwrap(), "main.wren");
+        assert_eq!(
+            archive_name(&Path::new("assets").join("textures").join("stone.png")).unwrap(),
+            "assets/textures/stone.png"
+        );
+
+        for path in [
+            "/main.wren",
+            "../main.wren",
+            "data/../main.wren",
+            "./main.wren",
+        ] {
+            let err = archive_name(Path::new(path)).unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::InvalidInput, "{path}");
+        }
+    }
+
+    #[test]
+    fn dir_reads_stay_inside_the_module() {
+        let dir = TempDir::new();
+        dir.write("outside.txt", "secret");
+        dir.write("core/main.wren", "System.print(1)");
+        let source = ModuleSource::Dir(dir.path().join("core"));
+
+        assert_eq!(
+            source.read_to_string("main.wren".as_ref()).unwrap(),
+            "System.print(1)"
+        );
+
+        let err = source.read("../outside.txt".as_ref()).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+        assert_eq!(
+            source.read("missing.wren".as_ref()).unwrap_err().kind(),
+            io::ErrorKind::NotFound
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn dir_reads_dont_follow_symlinks_out_of_the_module() {
+        let dir = TempDir::new();
+        let outside = dir.write("outside.txt", "secret");
+        dir.write("core/main.wren", "");
+        std::os::unix::fs::symlink(&outside, dir.path().join("core/link.txt")).unwrap();
+        std::os::unix::fs::symlink("main.wren", dir.path().join("core/inside.wren")).unwrap();
+        let source = ModuleSource::Dir(dir.path().join("core"));
+
+        let err = source.read("link.txt".as_ref()).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+        source.read("inside.wren".as_ref()).unwrap();
+    }
+}
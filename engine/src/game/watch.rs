@@ -0,0 +1,78 @@
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::{Path, PathBuf},
+    time::{Duration, Instant, SystemTime},
+};
+
+use super::source::ModuleSource;
+
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+// Watches module sources for changes by polling the modification times of
+// their files. Only used for debug sessions, so it favours being simple and
+// portable over being fast.
+pub struct ModuleWatcher {
+    // The modification time of every file, keyed by module source path.
+    snapshots: BTreeMap<PathBuf, BTreeMap<PathBuf, SystemTime>>,
+    last_poll: Instant,
+}
+
+impl ModuleWatcher {
+    pub fn new<'a>(sources: impl IntoIterator<Item = &'a ModuleSource>) -> Self {
+        Self {
+            snapshots: sources
+                .into_iter()
+                .map(|source| (source.path().to_path_buf(), snapshot(source.path())))
+                .collect(),
+            last_poll: Instant::now(),
+        }
+    }
+
+    // Returns the paths of the module sources that changed since the last
+    // poll. Cheap to call every frame, the files are only checked every
+    // `POLL_INTERVAL`.
+    pub fn poll(&mut self) -> Vec<PathBuf> {
+        if self.last_poll.elapsed() < POLL_INTERVAL {
+            return vec![];
+        }
+        self.last_poll = Instant::now();
+
+        self.snapshots
+            .iter_mut()
+            .filter_map(|(path, files)| {
+                let current = snapshot(path);
+                if current == *files {
+                    return None;
+                }
+
+                *files = current;
+                Some(path.clone())
+            })
+            .collect()
+    }
+}
+
+// A file that can't be read is left out rather than failing the snapshot, it's
+// most likely being written to and will show up in the next one.
+fn snapshot(path: &Path) -> BTreeMap<PathBuf, SystemTime> {
+    let mut files = BTreeMap::new();
+    let mut pending = vec![path.to_path_buf()];
+
+    while let Some(path) = pending.pop() {
+        let Ok(metadata) = fs::metadata(&path) else {
+            continue;
+        };
+
+        if metadata.is_dir() {
+            let Ok(entries) = fs::read_dir(&path) else {
+                continue;
+            };
+            pending.extend(entries.filter_map(|entry| Some(entry.ok()?.path())));
+        } else if let Ok(modified) = metadata.modified() {
+            files.insert(path, modified);
+        }
+    }
+
+    files
+}
@@ -0,0 +1,77 @@
+use ruwren::VMWrapper;
+use std::{
+    sync::mpsc::{self, RecvTimeoutError},
+    thread,
+    time::Duration,
+};
+
+// How long a single script call may run. Set with `script_budget` in the
+// profile.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ScriptBudget {
+    // A module's entrypoint.
+    pub load: Duration,
+    // An event handler or command, which usually run every tick.
+    pub handler: Duration,
+}
+
+impl Default for ScriptBudget {
+    fn default() -> Self {
+        Self {
+            load: Duration::from_secs(2),
+            handler: Duration::from_millis(20),
+        }
+    }
+}
+
+type Job = Box<dyn FnOnce(&VMWrapper) + Send>;
+
+// A thread that owns a module's Wren VM and runs calls on it. Wren can't be
+// interrupted from the outside, so the host only waits for a call until its
+// budget runs out. A call that runs over keeps its thread busy, so the worker
+// can't be used afterwards.
+pub struct Worker {
+    jobs: mpsc::Sender<Job>,
+}
+
+impl Worker {
+    // VMs can't move between threads, so `init` creates it on the worker.
+    pub fn spawn(name: String, init: impl FnOnce() -> VMWrapper + Send + 'static) -> Self {
+        let (jobs, received) = mpsc::channel::<Job>();
+
+        thread::Builder::new()
+            .name(name)
+            .spawn(move || {
+                let vm = init();
+                // Ends once the worker is dropped.
+                for job in received {
+                    job(&vm);
+                }
+            })
+            .expect("failed to start a script worker thread");
+
+        Self { jobs }
+    }
+
+    // Runs `f` on the worker's VM, or returns `None` if it didn't return
+    // within `budget`.
+    pub fn run<T: Send + 'static>(
+        &self,
+        budget: Duration,
+        f: impl FnOnce(&VMWrapper) -> T + Send + 'static,
+    ) -> Option<T> {
+        let (sender, result) = mpsc::sync_channel(1);
+        self.jobs
+            .send(Box::new(move |vm| {
+                // Nobody is waiting anymore if the call ran over.
+                let _ = sender.send(f(vm));
+            }))
+            .expect("script worker thread stopped");
+
+        match result.recv_timeout(budget) {
+            Ok(result) => Some(result),
+            Err(RecvTimeoutError::Timeout) => None,
+            Err(RecvTimeoutError::Disconnected) => panic!("script worker thread panicked"),
+        }
+    }
+}
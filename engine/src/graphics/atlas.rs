@@ -0,0 +1,214 @@
+use image::{Rgba, RgbaImage};
+use std::{collections::HashMap, fmt};
+use texture_packer::{exporter::ImageExporter, TexturePacker, TexturePackerConfig};
+
+pub const MISSING_TEXTURE: &str = "engine:missing";
+
+const MAX_SIZE: u32 = 8192;
+
+#[derive(Debug)]
+pub enum AtlasError {
+    TooLarge { key: String },
+}
+
+impl fmt::Display for AtlasError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::TooLarge { key } => write!(
+                f,
+                "texture {key} doesn't fit into a {MAX_SIZE}x{MAX_SIZE} texture atlas"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for AtlasError {}
+
+// Where a texture ended up in the atlas, in normalized atlas coordinates.
+#[derive(Clone, Copy, Debug)]
+pub struct AtlasRegion {
+    pub min: cgmath::Point2<f32>,
+    pub max: cgmath::Point2<f32>,
+}
+
+impl AtlasRegion {
+    // Maps texture coordinates local to this texture (0..1) into the atlas.
+    pub fn uv(&self, tex_coords: cgmath::Point2<f32>) -> cgmath::Point2<f32> {
+        cgmath::point2(
+            self.min.x + (self.max.x - self.min.x) * tex_coords.x,
+            self.min.y + (self.max.y - self.min.y) * tex_coords.y,
+        )
+    }
+}
+
+// Every texture registered by the loaded modules packed into a single image,
+// keyed by `module:path`.
+pub struct TextureAtlas {
+    image: RgbaImage,
+    regions: HashMap<String, AtlasRegion>,
+}
+
+impl TextureAtlas {
+    pub fn build(textures: Vec<(String, RgbaImage)>) -> Result<Self, AtlasError> {
+        let mut packer = TexturePacker::new_skyline(TexturePackerConfig {
+            max_width: MAX_SIZE,
+            max_height: MAX_SIZE,
+            allow_rotation: false,
+            trim: false,
+            texture_padding: 2,
+            texture_extrusion: 1,
+            ..Default::default()
+        });
+
+        for (key, image) in std::iter::once((MISSING_TEXTURE.to_string(), missing_texture()))
+            .chain(textures)
+        {
+            packer
+                .pack_own(key.clone(), image)
+                .map_err(|_| AtlasError::TooLarge { key })?;
+        }
+
+        let image = ImageExporter::export(&packer)
+            .expect("failed to export texture atlas")
+            .into_rgba8();
+        let (width, height) = (image.width() as f32, image.height() as f32);
+
+        let regions = packer
+            .get_frames()
+            .iter()
+            .map(|(key, frame)| {
+                let rect = frame.frame;
+                let region = AtlasRegion {
+                    min: cgmath::point2(rect.x as f32 / width, rect.y as f32 / height),
+                    max: cgmath::point2(
+                        (rect.x + rect.w) as f32 / width,
+                        (rect.y + rect.h) as f32 / height,
+                    ),
+                };
+                (key.clone(), region)
+            })
+            .collect();
+
+        Ok(Self { image, regions })
+    }
+
+    pub fn image(&self) -> &RgbaImage {
+        &self.image
+    }
+
+    pub fn region(&self, key: &str) -> Option<AtlasRegion> {
+        self.regions.get(key).copied()
+    }
+
+    // Like `region`, but falls back to the missing texture for unknown keys.
+    pub fn uv(&self, key: &str, tex_coords: cgmath::Point2<f32>) -> cgmath::Point2<f32> {
+        self.region(key)
+            .or_else(|| self.region(MISSING_TEXTURE))
+            .unwrap()
+            .uv(tex_coords)
+    }
+}
+
+fn missing_texture() -> RgbaImage {
+    RgbaImage::from_fn(16, 16, |x, y| {
+        if (x / 8 + y / 8) % 2 == 0 {
+            Rgba([255, 0, 255, 255])
+        } else {
+            Rgba([0, 0, 0, 255])
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn solid(width: u32, height: u32, color: [u8; 4]) -> RgbaImage {
+        RgbaImage::from_pixel(width, height, Rgba(color))
+    }
+
+    // The pixels of the atlas image a region covers.
+    fn pixels(atlas: &TextureAtlas, region: AtlasRegion) -> Vec<Rgba<u8>> {
+        let (width, height) = (atlas.image().width() as f32, atlas.image().height() as f32);
+        let (x0, y0) = (
+            (region.min.x * width) as u32,
+            (region.min.y * height) as u32,
+        );
+        let (x1, y1) = (
+            (region.max.x * width) as u32,
+            (region.max.y * height) as u32,
+        );
+
+        (y0..y1)
+            .flat_map(|y| (x0..x1).map(move |x| *atlas.image().get_pixel(x, y)))
+            .collect()
+    }
+
+    #[test]
+    fn packs_every_texture() {
+        let red = [255, 0, 0, 255];
+        let blue = [0, 0, 255, 255];
+        let atlas = TextureAtlas::build(vec![
+            ("core:red".to_string(), solid(4, 4, red)),
+            ("core:blue".to_string(), solid(8, 2, blue)),
+        ])
+        .unwrap();
+
+        let red_region = atlas.region("core:red").unwrap();
+        let blue_region = atlas.region("core:blue").unwrap();
+        let red_pixels = pixels(&atlas, red_region);
+        assert_eq!(red_pixels.len(), 16);
+        assert!(red_pixels.iter().all(|pixel| pixel.0 == red));
+        let blue_pixels = pixels(&atlas, blue_region);
+        assert_eq!(blue_pixels.len(), 16);
+        assert!(blue_pixels.iter().all(|pixel| pixel.0 == blue));
+
+        let missing = atlas.region(MISSING_TEXTURE).unwrap();
+        assert_eq!(
+            pixels(&atlas, missing),
+            missing_texture().pixels().copied().collect::<Vec<_>>()
+        );
+
+        let overlap = |a: AtlasRegion, b: AtlasRegion| {
+            a.min.x < b.max.x && b.min.x < a.max.x && a.min.y < b.max.y && b.min.y < a.max.y
+        };
+        assert!(!overlap(red_region, blue_region));
+        assert!(!overlap(red_region, missing));
+        assert!(!overlap(blue_region, missing));
+    }
+
+    #[test]
+    fn uv_maps_into_the_region() {
+        let atlas =
+            TextureAtlas::build(vec![("core:red".to_string(), solid(4, 4, [255; 4]))]).unwrap();
+        let region = atlas.region("core:red").unwrap();
+
+        assert_eq!(atlas.uv("core:red", cgmath::point2(0.0, 0.0)), region.min);
+        assert_eq!(atlas.uv("core:red", cgmath::point2(1.0, 1.0)), region.max);
+        let center = atlas.uv("core:red", cgmath::point2(0.5, 0.5));
+        assert_eq!(
+            center,
+            cgmath::point2(
+                (region.min.x + region.max.x) / 2.0,
+                (region.min.y + region.max.y) / 2.0
+            )
+        );
+
+        let missing = atlas.region(MISSING_TEXTURE).unwrap();
+        assert_eq!(atlas.uv("core:gone", cgmath::point2(1.0, 1.0)), missing.max);
+    }
+
+    #[test]
+    fn textures_too_large_for_the_atlas() {
+        let err = TextureAtlas::build(vec![(
+            "core:huge".to_string(),
+            solid(MAX_SIZE + 1, 1, [0; 4]),
+        )])
+        .err()
+        .unwrap();
+        assert!(
+            matches!(&err, AtlasError::TooLarge { key } if key == "core:huge"),
+            "{err}"
+        );
+    }
+}
@@ -0,0 +1,379 @@
+// Golden-image tests: scenes are rendered offscreen and compared with reference
+// PNGs in `tests/golden`. Rendering needs a Vulkan driver, so those tests are
+// ignored by default, see `just golden`.
+
+use cgmath::Rotation3;
+use image::{DynamicImage, Rgba, RgbaImage};
+use std::{env, fs, path::PathBuf};
+
+use super::{
+    vulkan::VulkanGraphicsInterface, Camera, GraphicsInterface, Renderable, Transform, Vertex,
+};
+
+// How far each channel of a pixel may be off, so references rendered with one
+// driver still match the output of another.
+const TOLERANCE: u8 = 2;
+const EXTENT: [u32; 2] = [64, 64];
+
+#[derive(Clone)]
+pub struct MeshVertex {
+    pub point: cgmath::Point3<f32>,
+    pub tex_coords: cgmath::Point2<f32>,
+}
+
+impl Vertex for MeshVertex {
+    fn get_point(&self) -> cgmath::Point3<f32> {
+        self.point
+    }
+
+    fn get_tex_coords(&self) -> cgmath::Point2<f32> {
+        self.tex_coords
+    }
+}
+
+pub struct Mesh {
+    pub vertices: Vec<MeshVertex>,
+    pub indices: Vec<u32>,
+}
+
+impl Renderable<MeshVertex> for Mesh {
+    fn get_vertices(&self) -> Vec<MeshVertex> {
+        self.vertices.clone()
+    }
+
+    fn get_indices(&self) -> Vec<u32> {
+        self.indices.clone()
+    }
+}
+
+pub struct Scene {
+    pub extent: [u32; 2],
+    pub texture: RgbaImage,
+    // Added in order, so later meshes get later ids.
+    pub meshes: Vec<Mesh>,
+    pub camera: Camera,
+}
+
+impl Scene {
+    pub fn render(self) -> RgbaImage {
+        self.render_with(|_| {})
+    }
+
+    // Lets `change` update or remove meshes after a first frame was rendered
+    // with all of them, then renders another one.
+    pub fn render_with(self, change: impl FnOnce(&mut VulkanGraphicsInterface)) -> RgbaImage {
+        let mut interface = VulkanGraphicsInterface::new_offscreen(
+            self.extent,
+            DynamicImage::ImageRgba8(self.texture),
+        );
+        for mesh in self.meshes {
+            interface.add_renderable(mesh);
+        }
+
+        interface.render(camera());
+        change(&mut interface);
+
+        interface.render(self.camera);
+        interface.read_image()
+    }
+}
+
+pub struct Mismatch {
+    // Pixels with a channel more than the tolerance off.
+    pub pixels: usize,
+    pub max_difference: u8,
+    // Differing pixels in red over a faded copy of the expected image.
+    pub diff: RgbaImage,
+}
+
+pub fn compare(actual: &RgbaImage, expected: &RgbaImage, tolerance: u8) -> Result<(), Mismatch> {
+    assert_eq!(
+        actual.dimensions(),
+        expected.dimensions(),
+        "images have different sizes"
+    );
+
+    let mut pixels = 0;
+    let mut max_difference = 0;
+    let mut diff = RgbaImage::new(expected.width(), expected.height());
+
+    for (x, y, expected_pixel) in expected.enumerate_pixels() {
+        let actual_pixel = actual.get_pixel(x, y);
+        let difference = (0..4)
+            .map(|c| actual_pixel[c].abs_diff(expected_pixel[c]))
+            .max()
+            .unwrap();
+        max_difference = max_difference.max(difference);
+
+        let pixel = if difference > tolerance {
+            pixels += 1;
+            Rgba([255, 0, 0, 255])
+        } else {
+            let [r, g, b, _] = expected_pixel.0;
+            Rgba([r / 4, g / 4, b / 4, 255])
+        };
+        diff.put_pixel(x, y, pixel);
+    }
+
+    if pixels == 0 {
+        Ok(())
+    } else {
+        Err(Mismatch {
+            pixels,
+            max_difference,
+            diff,
+        })
+    }
+}
+
+// Renders `scene` and compares it with `tests/golden/<name>.png`. A missing
+// reference is written out for review and fails the test; set `UPDATE_GOLDEN=1`
+// to replace references after an intended change. On a mismatch the actual
+// and diff images are written to `target/golden`.
+pub fn check(name: &str, scene: Scene) {
+    check_image(name, scene.render());
+}
+
+// Like `check`, for images rendered with `Scene::render_with`.
+pub fn check_image(name: &str, actual: RgbaImage) {
+    let root = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    let reference = root.join("tests/golden").join(format!("{name}.png"));
+    let output_dir = root.join("target/golden");
+
+    if env::var_os("UPDATE_GOLDEN").is_some() || !reference.exists() {
+        let missing = !reference.exists();
+        fs::create_dir_all(reference.parent().unwrap()).unwrap();
+        actual.save(&reference).unwrap();
+
+        assert!(
+            !missing,
+            "no reference for {name}, wrote {}, check it and commit it",
+            reference.display()
+        );
+        return;
+    }
+
+    let expected = image::open(&reference).unwrap().into_rgba8();
+    if let Err(mismatch) = compare(&actual, &expected, TOLERANCE) {
+        fs::create_dir_all(&output_dir).unwrap();
+        let actual_path = output_dir.join(format!("{name}.actual.png"));
+        let diff_path = output_dir.join(format!("{name}.diff.png"));
+        actual.save(&actual_path).unwrap();
+        mismatch.diff.save(&diff_path).unwrap();
+
+        panic!(
+            "{name}: {} pixels differ from {} by up to {}, see {} and {}",
+            mismatch.pixels,
+            reference.display(),
+            mismatch.max_difference,
+            actual_path.display(),
+            diff_path.display()
+        );
+    }
+}
+
+// The camera `main` starts with, looking down +z from the origin.
+fn camera() -> Camera {
+    Camera {
+        theta_x: 0.0,
+        theta_y: 0.0,
+        fov: (70.0f32).to_radians(),
+        near_cutoff: 0.01,
+        far_cutoff: 100.0,
+        eye: cgmath::point3(0.0, 0.0, 0.0),
+        center: cgmath::point3(0.0, 0.0, 1.0),
+        up: cgmath::vec3(0.0, 1.0, 0.0),
+        scale: 1.0,
+    }
+}
+
+fn triangle(z: f32, uv: [[f32; 2]; 3]) -> Mesh {
+    let points = [[0.0, 1.0], [-1.0, -1.0], [1.0, -1.0]];
+
+    Mesh {
+        vertices: points
+            .iter()
+            .zip(uv)
+            .map(|([x, y], [u, v])| MeshVertex {
+                point: cgmath::point3(*x, *y, z),
+                tex_coords: cgmath::point2(u, v),
+            })
+            .collect(),
+        indices: vec![0, 1, 2],
+    }
+}
+
+// Red, green, blue and white quadrants.
+fn checker() -> RgbaImage {
+    RgbaImage::from_fn(2, 2, |x, y| match (x, y) {
+        (0, 0) => Rgba([255, 0, 0, 255]),
+        (1, 0) => Rgba([0, 255, 0, 255]),
+        (0, 1) => Rgba([0, 0, 255, 255]),
+        _ => Rgba([255, 255, 255, 255]),
+    })
+}
+
+#[test]
+fn compare_within_tolerance() {
+    let expected = checker();
+    let mut actual = checker();
+    actual.put_pixel(1, 1, Rgba([253, 255, 254, 255]));
+
+    assert!(compare(&actual, &expected, TOLERANCE).is_ok());
+}
+
+#[test]
+fn compare_over_tolerance() {
+    let expected = checker();
+    let mut actual = checker();
+    actual.put_pixel(0, 1, Rgba([0, 0, 250, 255]));
+    actual.put_pixel(1, 1, Rgba([255, 255, 255, 200]));
+
+    let mismatch = compare(&actual, &expected, TOLERANCE).unwrap_err();
+    assert_eq!(mismatch.pixels, 2);
+    assert_eq!(mismatch.max_difference, 55);
+    assert_eq!(*mismatch.diff.get_pixel(0, 1), Rgba([255, 0, 0, 255]));
+    assert_eq!(*mismatch.diff.get_pixel(0, 0), Rgba([63, 0, 0, 255]));
+}
+
+#[test]
+#[should_panic(expected = "images have different sizes")]
+fn compare_size_mismatch() {
+    let _ = compare(&RgbaImage::new(2, 3), &checker(), TOLERANCE);
+}
+
+#[test]
+#[ignore = "needs a Vulkan driver, run with `just golden`"]
+fn golden_textured_triangle() {
+    check(
+        "textured_triangle",
+        Scene {
+            extent: EXTENT,
+            texture: checker(),
+            meshes: vec![triangle(2.0, [[0.5, 0.0], [0.0, 1.0], [1.0, 1.0]])],
+            camera: camera(),
+        },
+    );
+}
+
+// The far triangle is added last, so it only stays hidden if depth testing
+// works.
+#[test]
+#[ignore = "needs a Vulkan driver, run with `just golden`"]
+fn golden_depth() {
+    let red = [[0.25, 0.25]; 3];
+    let green = [[0.75, 0.25]; 3];
+
+    let mut far = triangle(4.0, green);
+    for vertex in &mut far.vertices {
+        vertex.point.y -= 1.0;
+    }
+
+    check(
+        "depth",
+        Scene {
+            extent: EXTENT,
+            texture: checker(),
+            meshes: vec![triangle(2.0, red), far],
+            camera: camera(),
+        },
+    );
+}
+
+// Ids 0 and 1 are removed and updated between frames, leaving a smaller copy
+// of the second triangle and a third one. The removed id can't be reused until
+// the next frame has started, so the third one gets a new id.
+#[test]
+#[ignore = "needs a Vulkan driver, run with `just golden`"]
+fn golden_update_and_remove() {
+    let red = [[0.25, 0.25]; 3];
+    let green = [[0.75, 0.25]; 3];
+    let blue = [[0.25, 0.75]; 3];
+
+    let scene = Scene {
+        extent: EXTENT,
+        texture: checker(),
+        meshes: vec![triangle(2.0, red), triangle(3.0, green)],
+        camera: camera(),
+    };
+
+    let image = scene.render_with(|interface| {
+        interface.rm_renderable(0);
+
+        let mut small = triangle(3.0, green);
+        for vertex in &mut small.vertices {
+            vertex.point.x *= 0.5;
+            vertex.point.y *= 0.5;
+        }
+        interface.update_renderable(1, small);
+
+        let mut right = triangle(4.0, blue);
+        for vertex in &mut right.vertices {
+            vertex.point.x += 1.5;
+        }
+        assert_eq!(interface.add_renderable(right), 2);
+    });
+
+    check_image("update_and_remove", image);
+}
+
+// The same triangle twice, moved apart, one turned upside down and shrunk.
+#[test]
+#[ignore = "needs a Vulkan driver, run with `just golden`"]
+fn golden_transforms() {
+    let scene = Scene {
+        extent: EXTENT,
+        texture: checker(),
+        meshes: vec![
+            triangle(0.0, [[0.5, 0.0], [0.0, 1.0], [1.0, 1.0]]),
+            triangle(0.0, [[0.5, 0.0], [0.0, 1.0], [1.0, 1.0]]),
+        ],
+        camera: camera(),
+    };
+
+    let image = scene.render_with(|interface| {
+        interface.set_transform(
+            0,
+            Transform {
+                translation: cgmath::vec3(-1.0, 0.0, 3.0),
+                ..Default::default()
+            },
+        );
+        interface.set_transform(
+            1,
+            Transform {
+                translation: cgmath::vec3(1.0, 0.0, 3.0),
+                rotation: cgmath::Quaternion::from_angle_z(cgmath::Deg(180.0)),
+                scale: cgmath::vec3(0.5, 0.5, 0.5),
+            },
+        );
+    });
+
+    check_image("transforms", image);
+}
+
+// The camera is turned 45° towards +x, so the triangle at x = 2 is straight
+// ahead and the one in front of the origin is off to the side.
+#[test]
+#[ignore = "needs a Vulkan driver, run with `just golden`"]
+fn golden_rotated_camera() {
+    let red = [[0.25, 0.25]; 3];
+
+    let mut ahead = triangle(2.0, [[0.5, 0.0], [0.0, 1.0], [1.0, 1.0]]);
+    for vertex in &mut ahead.vertices {
+        vertex.point.x += 2.0;
+    }
+
+    check(
+        "rotated_camera",
+        Scene {
+            extent: EXTENT,
+            texture: checker(),
+            meshes: vec![triangle(2.0, red), ahead],
+            camera: Camera {
+                theta_y: (45.0f32).to_radians(),
+                ..camera()
+            },
+        },
+    );
+}
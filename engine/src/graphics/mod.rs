@@ -1,6 +1,10 @@
+pub mod atlas;
+#[cfg(test)]
+mod golden;
+pub mod null;
 pub mod vulkan;
 
-use std::sync::{Arc, Mutex};
+use std::sync::Arc;
 use winit::{dpi::PhysicalSize, event_loop::EventLoop, window::Window};
 
 pub struct Camera {
@@ -18,6 +22,33 @@ pub struct Camera {
     pub scale: f32,
 }
 
+// Where a renderable is drawn. Applied as scale, then rotation, then
+// translation.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Transform {
+    pub translation: cgmath::Vector3<f32>,
+    pub rotation: cgmath::Quaternion<f32>,
+    pub scale: cgmath::Vector3<f32>,
+}
+
+impl Default for Transform {
+    fn default() -> Self {
+        Self {
+            translation: cgmath::vec3(0.0, 0.0, 0.0),
+            rotation: cgmath::Quaternion::new(1.0, 0.0, 0.0, 0.0),
+            scale: cgmath::vec3(1.0, 1.0, 1.0),
+        }
+    }
+}
+
+impl Transform {
+    pub fn matrix(&self) -> cgmath::Matrix4<f32> {
+        cgmath::Matrix4::from_translation(self.translation)
+            * cgmath::Matrix4::from(self.rotation)
+            * cgmath::Matrix4::from_nonuniform_scale(self.scale.x, self.scale.y, self.scale.z)
+    }
+}
+
 pub trait Vertex {
     fn get_point(&self) -> cgmath::Point3<f32>;
     fn get_tex_coords(&self) -> cgmath::Point2<f32>;
@@ -29,24 +60,22 @@ pub trait Renderable<V: Vertex> {
 }
 
 pub trait GraphicsInterface {
-    fn new(event_loop: EventLoop<()>, window: Window, texture: image::DynamicImage) -> Self;
+    fn new(event_loop: &EventLoop<()>, window: Arc<Window>, texture: image::DynamicImage) -> Self;
 
     fn add_renderable<V: Vertex>(&mut self, renderable: impl Renderable<V> + Send) -> usize;
+    // Replaces the geometry of renderable `id`, keeping its id.
+    fn update_renderable<V: Vertex>(&mut self, id: usize, renderable: impl Renderable<V> + Send);
+    // Frees `id` for reuse once no frame in flight uses the renderable anymore.
+    // Nothing in the engine removes renderables yet, only the tests do.
+    #[allow(dead_code)]
     fn rm_renderable(&mut self, id: usize);
+    // Renderables start out with the identity transform.
+    fn set_transform(&mut self, id: usize, transform: Transform);
+
+    // Replaces the texture every renderable samples from.
+    fn set_texture(&mut self, texture: image::DynamicImage);
 
     fn render(&mut self, camera: Camera);
 
     fn on_resized(&mut self, new_size: PhysicalSize<u32>);
-
-    fn event_loop()
 }
-
-pub struct GraphicsLoop<I: GraphicsInterface> {
-    interface: I
-}
-
-impl GraphicsLoop {
-    fn new(interface: dyn GraphicsInterface) -> Self {
-        todo!()
-    }
-}
\ No newline at end of file
@@ -0,0 +1,236 @@
+use std::sync::Arc;
+use winit::{dpi::PhysicalSize, event_loop::EventLoop, window::Window};
+
+use crate::graphics::{Camera, GraphicsInterface, Renderable, Transform, Vertex};
+
+// What a `NullGraphicsInterface` has been asked to draw.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct NullStats {
+    // Renderables that were added and not removed yet.
+    pub renderables: usize,
+    // Totals over those renderables.
+    pub vertices: usize,
+    pub indices: usize,
+    // Calls to `render`.
+    pub frames: u64,
+}
+
+#[derive(Clone, Copy)]
+enum Slot {
+    Free,
+    // (vertices, indices)
+    Used(usize, usize),
+    // Removed since the last frame, freed by `render` like with Vulkan.
+    Retired,
+}
+
+// A graphics interface that draws nothing, for running the game without a
+// Vulkan driver, like in tests or on servers. `default()` creates one without
+// a window. Renderables get ids the same way they do with
+// `VulkanGraphicsInterface`.
+#[derive(Default)]
+pub struct NullGraphicsInterface {
+    renderables: Vec<Slot>,
+    stats: NullStats,
+}
+
+impl NullGraphicsInterface {
+    pub fn stats(&self) -> NullStats {
+        self.stats
+    }
+}
+
+impl GraphicsInterface for NullGraphicsInterface {
+    fn new(
+        _event_loop: &EventLoop<()>,
+        _window: Arc<Window>,
+        _texture: image::DynamicImage,
+    ) -> Self {
+        Self::default()
+    }
+
+    fn add_renderable<V: Vertex>(&mut self, renderable: impl Renderable<V> + Send) -> usize {
+        let index = match self
+            .renderables
+            .iter()
+            .position(|x| matches!(x, Slot::Free))
+        {
+            Some(idx) => idx,
+            None => {
+                self.renderables.push(Slot::Free);
+                self.renderables.len() - 1
+            }
+        };
+
+        let vertices = renderable.get_vertices().len();
+        let indices = renderable.get_indices().len();
+        self.renderables[index] = Slot::Used(vertices, indices);
+
+        self.stats.renderables += 1;
+        self.stats.vertices += vertices;
+        self.stats.indices += indices;
+
+        index
+    }
+
+    fn update_renderable<V: Vertex>(&mut self, id: usize, renderable: impl Renderable<V> + Send) {
+        let Slot::Used(old_vertices, old_indices) = &mut self.renderables[id] else {
+            panic!("renderable {id} doesn't exist");
+        };
+
+        let vertices = renderable.get_vertices().len();
+        let indices = renderable.get_indices().len();
+        self.stats.vertices = self.stats.vertices - *old_vertices + vertices;
+        self.stats.indices = self.stats.indices - *old_indices + indices;
+        *old_vertices = vertices;
+        *old_indices = indices;
+    }
+
+    fn rm_renderable(&mut self, id: usize) {
+        let Slot::Used(vertices, indices) = self.renderables[id] else {
+            panic!("renderable {id} doesn't exist");
+        };
+        self.renderables[id] = Slot::Retired;
+
+        self.stats.renderables -= 1;
+        self.stats.vertices -= vertices;
+        self.stats.indices -= indices;
+    }
+
+    fn set_transform(&mut self, id: usize, _transform: Transform) {
+        assert!(
+            matches!(self.renderables[id], Slot::Used(..)),
+            "renderable {id} doesn't exist"
+        );
+    }
+
+    fn set_texture(&mut self, _texture: image::DynamicImage) {}
+
+    fn render(&mut self, _camera: Camera) {
+        for slot in &mut self.renderables {
+            if matches!(slot, Slot::Retired) {
+                *slot = Slot::Free;
+            }
+        }
+
+        self.stats.frames += 1;
+    }
+
+    fn on_resized(&mut self, _new_size: PhysicalSize<u32>) {}
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Clone)]
+    struct Point;
+
+    impl Vertex for Point {
+        fn get_point(&self) -> cgmath::Point3<f32> {
+            cgmath::point3(0.0, 0.0, 0.0)
+        }
+
+        fn get_tex_coords(&self) -> cgmath::Point2<f32> {
+            cgmath::point2(0.0, 0.0)
+        }
+    }
+
+    struct Mesh {
+        vertices: usize,
+        indices: usize,
+    }
+
+    impl Renderable<Point> for Mesh {
+        fn get_vertices(&self) -> Vec<Point> {
+            vec![Point; self.vertices]
+        }
+
+        fn get_indices(&self) -> Vec<u32> {
+            vec![0; self.indices]
+        }
+    }
+
+    fn mesh(vertices: usize, indices: usize) -> Mesh {
+        Mesh { vertices, indices }
+    }
+
+    fn camera() -> Camera {
+        Camera {
+            theta_x: 0.0,
+            theta_y: 0.0,
+            fov: 1.0,
+            near_cutoff: 0.01,
+            far_cutoff: 100.0,
+            eye: cgmath::point3(0.0, 0.0, 0.0),
+            center: cgmath::point3(0.0, 0.0, 1.0),
+            up: cgmath::vec3(0.0, 1.0, 0.0),
+            scale: 1.0,
+        }
+    }
+
+    #[test]
+    fn removed_ids_are_reused_after_a_frame() {
+        let mut graphics = NullGraphicsInterface::default();
+        let a = graphics.add_renderable(mesh(3, 3));
+        let b = graphics.add_renderable(mesh(3, 3));
+        assert_ne!(a, b);
+
+        graphics.rm_renderable(a);
+        let c = graphics.add_renderable(mesh(3, 3));
+        assert_ne!(c, a, "id reused while a frame may still draw it");
+        assert_ne!(c, b);
+
+        graphics.render(camera());
+        assert_eq!(graphics.add_renderable(mesh(3, 3)), a);
+    }
+
+    #[test]
+    #[should_panic(expected = "renderable 0 doesn't exist")]
+    fn removing_twice_panics() {
+        let mut graphics = NullGraphicsInterface::default();
+        let a = graphics.add_renderable(mesh(3, 3));
+        graphics.rm_renderable(a);
+        graphics.render(camera());
+        graphics.rm_renderable(a);
+    }
+
+    #[test]
+    #[should_panic(expected = "renderable 0 doesn't exist")]
+    fn removing_a_retired_id_panics() {
+        let mut graphics = NullGraphicsInterface::default();
+        let a = graphics.add_renderable(mesh(3, 3));
+        graphics.rm_renderable(a);
+        graphics.rm_renderable(a);
+    }
+
+    #[test]
+    fn update_adjusts_stats() {
+        let mut graphics = NullGraphicsInterface::default();
+        let a = graphics.add_renderable(mesh(3, 3));
+        graphics.add_renderable(mesh(4, 6));
+
+        graphics.update_renderable(a, mesh(8, 12));
+        assert_eq!(
+            graphics.stats(),
+            NullStats {
+                renderables: 2,
+                vertices: 12,
+                indices: 18,
+                frames: 0,
+            }
+        );
+
+        graphics.rm_renderable(a);
+        graphics.render(camera());
+        assert_eq!(
+            graphics.stats(),
+            NullStats {
+                renderables: 1,
+                vertices: 4,
+                indices: 6,
+                frames: 1,
+            }
+        );
+    }
+}
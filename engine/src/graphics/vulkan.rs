@@ -1,5 +1,5 @@
-use cgmath::{Matrix3, Matrix4, Rad};
-use std::sync::Arc;
+use cgmath::{Matrix, Matrix3, Matrix4, Rad};
+use std::{mem, sync::Arc};
 use vulkano::{
     buffer::{
         allocator::{SubbufferAllocator, SubbufferAllocatorCreateInfo},
@@ -7,7 +7,8 @@ use vulkano::{
     },
     command_buffer::{
         allocator::StandardCommandBufferAllocator, AutoCommandBufferBuilder, CommandBufferUsage,
-        CopyBufferToImageInfo, PrimaryCommandBufferAbstract, RenderPassBeginInfo,
+        CopyBufferToImageInfo, PrimaryAutoCommandBuffer, PrimaryCommandBufferAbstract,
+        RenderPassBeginInfo,
     },
     descriptor_set::{
         allocator::StandardDescriptorSetAllocator, PersistentDescriptorSet, WriteDescriptorSet,
@@ -22,7 +23,7 @@ use vulkano::{
         view::ImageView,
         Image, ImageCreateInfo, ImageType, ImageUsage,
     },
-    instance::{Instance, InstanceCreateFlags, InstanceCreateInfo},
+    instance::{Instance, InstanceCreateFlags, InstanceCreateInfo, InstanceExtensions},
     memory::allocator::{AllocationCreateInfo, MemoryTypeFilter, StandardMemoryAllocator},
     pipeline::{
         graphics::{
@@ -49,7 +50,12 @@ use vulkano::{
 };
 use winit::{event_loop::EventLoop, window::Window};
 
-use crate::graphics::GraphicsInterface;
+use crate::graphics::{GraphicsInterface, Transform};
+
+// The format of the image offscreen interfaces render into, so it can be read
+// back as an `image::RgbaImage` as it is.
+#[cfg(test)]
+const OFFSCREEN_FORMAT: Format = Format::R8G8B8A8_SRGB;
 
 #[derive(BufferContents, VertexTrait)]
 #[repr(C)]
@@ -61,20 +67,47 @@ struct Vertex {
 }
 
 struct VulkanRenderable {
+    // Can be longer than the renderable needs after `update_renderable`
+    // shrinks it in place.
     vertex_buffer: Subbuffer<[Vertex]>,
     index_buffer: Subbuffer<[u32]>,
+    index_count: u32,
+    transform: Transform,
+}
+
+enum Slot {
+    Free,
+    Used(VulkanRenderable),
+    // Removed since the last frame, which may still be drawing it. Freed at
+    // the start of the next frame.
+    Retired,
+}
+
+// What an interface renders into.
+enum Target {
+    Window {
+        window: Arc<Window>,
+        swapchain: Arc<Swapchain>,
+    },
+    // A device-local image that is copied into `download` after every frame,
+    // see `VulkanGraphicsInterface::new_offscreen`.
+    #[cfg(test)]
+    Offscreen {
+        image: Arc<Image>,
+        download: Subbuffer<[u8]>,
+    },
 }
 
 pub struct VulkanGraphicsInterface {
-    window: Arc<Window>,
+    target: Target,
 
     device: Arc<Device>,
 
+    // One per swapchain image, or a single one when rendering offscreen.
     framebuffers: Vec<Arc<Framebuffer>>,
     pipeline: Arc<GraphicsPipeline>,
     queue: Arc<Queue>,
     render_pass: Arc<RenderPass>,
-    swapchain: Arc<Swapchain>,
     uniform_buffer: SubbufferAllocator,
 
     // Allocators
@@ -94,73 +127,23 @@ pub struct VulkanGraphicsInterface {
     recreate_swapchain: bool,
 
     // Render pool
-    renderables: Vec<Option<VulkanRenderable>>,
+    renderables: Vec<Slot>,
+    // Buffers that were removed or replaced since the last frame.
+    retired: Vec<VulkanRenderable>,
 }
 
 impl GraphicsInterface for VulkanGraphicsInterface {
     fn new(
-        event_loop: EventLoop<()>,
-        window: Window,
+        event_loop: &EventLoop<()>,
+        window: Arc<Window>,
         texture_image: image::DynamicImage,
     ) -> Self {
-        let library = VulkanLibrary::new().unwrap();
-        let required_extensions = Surface::required_extensions(&event_loop);
-
-        let instance = Instance::new(
-            library,
-            InstanceCreateInfo {
-                flags: InstanceCreateFlags::ENUMERATE_PORTABILITY,
-                enabled_extensions: required_extensions,
-                ..Default::default()
-            },
-        )
-        .unwrap();
+        let required_extensions = Surface::required_extensions(event_loop);
+        let instance = create_instance(required_extensions);
 
         let surface = Surface::from_window(instance.clone(), window.clone()).unwrap();
 
-        let device_extensions = DeviceExtensions {
-            khr_swapchain: true,
-            ..DeviceExtensions::empty()
-        };
-
-        let (physical_device, queue_family_index) = instance
-            .enumerate_physical_devices()
-            .unwrap()
-            .filter(|p| p.supported_extensions().contains(&device_extensions))
-            .filter_map(|p| {
-                p.queue_family_properties()
-                    .iter()
-                    .enumerate()
-                    .position(|(i, q)| {
-                        q.queue_flags.intersects(QueueFlags::GRAPHICS)
-                            && p.surface_support(i as u32, &surface).unwrap_or(false)
-                    })
-                    .map(|i| (p, i as u32))
-            })
-            .min_by_key(|(p, _)| match p.properties().device_type {
-                PhysicalDeviceType::DiscreteGpu => 0,
-                PhysicalDeviceType::IntegratedGpu => 1,
-                PhysicalDeviceType::VirtualGpu => 2,
-                PhysicalDeviceType::Cpu => 3,
-                PhysicalDeviceType::Other => 4,
-                _ => 5,
-            })
-            .expect("no suitable device found");
-
-        let (device, mut queues) = Device::new(
-            physical_device,
-            DeviceCreateInfo {
-                enabled_extensions: device_extensions,
-                queue_create_infos: vec![QueueCreateInfo {
-                    queue_family_index,
-                    ..Default::default()
-                }],
-                ..Default::default()
-            },
-        )
-        .unwrap();
-
-        let queue = queues.next().unwrap();
+        let (device, queue) = create_device(instance, Some(&surface));
 
         let (swapchain, images) = {
             let surface_capabilities = device
@@ -194,6 +177,276 @@ impl GraphicsInterface for VulkanGraphicsInterface {
         };
 
         let memory_allocator = Arc::new(StandardMemoryAllocator::new_default(device.clone()));
+
+        Self::with_target(
+            Target::Window { window, swapchain },
+            &images,
+            memory_allocator,
+            queue,
+            texture_image,
+        )
+    }
+
+    fn add_renderable<V: super::Vertex>(
+        &mut self,
+        renderable: impl super::Renderable<V> + Send,
+    ) -> usize {
+        let index = match self
+            .renderables
+            .iter()
+            .position(|x| matches!(x, Slot::Free))
+        {
+            Some(idx) => idx,
+            None => {
+                self.renderables.push(Slot::Free);
+                self.renderables.len() - 1
+            }
+        };
+
+        let vulkan_renderable =
+            self.create_renderable(vertices(&renderable), renderable.get_indices());
+        self.renderables[index] = Slot::Used(vulkan_renderable);
+
+        index
+    }
+
+    fn update_renderable<V: super::Vertex>(
+        &mut self,
+        id: usize,
+        renderable: impl super::Renderable<V> + Send,
+    ) {
+        let Slot::Used(old) = &mut self.renderables[id] else {
+            panic!("renderable {id} doesn't exist");
+        };
+        let transform = old.transform;
+
+        let vertices = vertices(&renderable);
+        let indices = renderable.get_indices();
+
+        // Rewriting the buffers only works if they're big enough and no frame
+        // is using them, otherwise they're replaced like on `add_renderable`.
+        if vertices.len() as u64 <= old.vertex_buffer.len()
+            && indices.len() as u64 <= old.index_buffer.len()
+        {
+            if let (Ok(mut vertex_data), Ok(mut index_data)) =
+                (old.vertex_buffer.write(), old.index_buffer.write())
+            {
+                for (slot, vertex) in vertex_data.iter_mut().zip(vertices) {
+                    *slot = vertex;
+                }
+                index_data[..indices.len()].copy_from_slice(&indices);
+                old.index_count = indices.len() as u32;
+
+                return;
+            }
+        }
+
+        let mut new = self.create_renderable(vertices, indices);
+        new.transform = transform;
+        if let Slot::Used(old) = mem::replace(&mut self.renderables[id], Slot::Used(new)) {
+            self.retired.push(old);
+        }
+    }
+
+    fn rm_renderable(&mut self, id: usize) {
+        let Slot::Used(old) = mem::replace(&mut self.renderables[id], Slot::Retired) else {
+            panic!("renderable {id} doesn't exist");
+        };
+        self.retired.push(old);
+    }
+
+    fn set_transform(&mut self, id: usize, transform: Transform) {
+        let Slot::Used(renderable) = &mut self.renderables[id] else {
+            panic!("renderable {id} doesn't exist");
+        };
+        renderable.transform = transform;
+    }
+
+    fn set_texture(&mut self, texture: image::DynamicImage) {
+        self.texture = upload_texture(
+            self.memory_allocator.clone(),
+            &self.command_buffer_allocator,
+            self.queue.clone(),
+            texture,
+        );
+    }
+
+    fn render(&mut self, camera: super::Camera) {
+        // Every frame is waited for before `render` returns, so nothing
+        // retired before this one is in use anymore.
+        self.retired.clear();
+        for slot in &mut self.renderables {
+            if matches!(slot, Slot::Retired) {
+                *slot = Slot::Free;
+            }
+        }
+
+        let (window, mut swapchain) = match &self.target {
+            Target::Window { window, swapchain } => (window.clone(), swapchain.clone()),
+            #[cfg(test)]
+            Target::Offscreen { .. } => {
+                let command_buffer = self.record_frame(camera, 0);
+
+                sync::now(self.device.clone())
+                    .then_execute(self.queue.clone(), command_buffer)
+                    .unwrap()
+                    .then_signal_fence_and_flush()
+                    .unwrap()
+                    .wait(None)
+                    .unwrap();
+
+                return;
+            }
+        };
+
+        let image_extent: [u32; 2] = window.inner_size().into();
+
+        if image_extent.contains(&0) {
+            return;
+        }
+
+        if self.recreate_swapchain {
+            let (new_swapchain, new_images) = swapchain
+                .recreate(SwapchainCreateInfo {
+                    image_extent,
+                    ..swapchain.create_info()
+                })
+                .expect("failed to recreate swapchain");
+
+            swapchain = new_swapchain;
+            self.target = Target::Window {
+                window,
+                swapchain: swapchain.clone(),
+            };
+            let (new_pipeline, new_framebuffers) = create_pipeline_and_framebuffers(
+                self.memory_allocator.clone(),
+                self.vs.clone(),
+                self.fs.clone(),
+                &new_images,
+                self.render_pass.clone(),
+            );
+            self.pipeline = new_pipeline;
+            self.framebuffers = new_framebuffers;
+            self.recreate_swapchain = false;
+        }
+
+        let (image_index, suboptimal, acquire_feature) =
+            match acquire_next_image(swapchain.clone(), None).map_err(Validated::unwrap) {
+                Ok(r) => r,
+                Err(VulkanError::OutOfDate) => {
+                    self.recreate_swapchain = true;
+                    return;
+                }
+                Err(e) => panic!("failed to acquire next image: {e}"),
+            };
+
+        if suboptimal {
+            self.recreate_swapchain = true;
+        }
+
+        let command_buffer = self.record_frame(camera, image_index as usize);
+
+        let execution = sync::now(self.device.clone())
+            .join(acquire_feature)
+            .then_execute(self.queue.clone(), command_buffer)
+            .unwrap()
+            .then_swapchain_present(
+                self.queue.clone(),
+                SwapchainPresentInfo::swapchain_image_index(swapchain, image_index),
+            )
+            .then_signal_fence_and_flush();
+
+        match execution.map_err(Validated::unwrap) {
+            Ok(future) => {
+                future.wait(None).unwrap();
+            }
+            Err(VulkanError::OutOfDate) => {
+                self.recreate_swapchain = true;
+            }
+            Err(e) => {
+                println!("failed to flush future: {e}");
+            }
+        }
+    }
+
+    fn on_resized(&mut self, _new_size: winit::dpi::PhysicalSize<u32>) {
+        // Offscreen images keep the size they were created with.
+        self.recreate_swapchain = matches!(self.target, Target::Window { .. });
+    }
+}
+
+impl VulkanGraphicsInterface {
+    // Renders into an image of `extent` pixels instead of a window, without
+    // needing a display. Works on CPU implementations like lavapipe, so
+    // renderables can be tested on headless machines. Read frames back with
+    // `read_image`.
+    #[cfg(test)]
+    pub fn new_offscreen(extent: [u32; 2], texture_image: image::DynamicImage) -> Self {
+        let instance = create_instance(InstanceExtensions::empty());
+        let (device, queue) = create_device(instance, None);
+
+        let memory_allocator = Arc::new(StandardMemoryAllocator::new_default(device.clone()));
+
+        let image = Image::new(
+            memory_allocator.clone(),
+            ImageCreateInfo {
+                image_type: ImageType::Dim2d,
+                format: OFFSCREEN_FORMAT,
+                extent: [extent[0], extent[1], 1],
+                usage: ImageUsage::COLOR_ATTACHMENT | ImageUsage::TRANSFER_SRC,
+                ..Default::default()
+            },
+            AllocationCreateInfo::default(),
+        )
+        .unwrap();
+
+        let download = Buffer::new_slice::<u8>(
+            memory_allocator.clone(),
+            BufferCreateInfo {
+                usage: BufferUsage::TRANSFER_DST,
+                ..Default::default()
+            },
+            AllocationCreateInfo {
+                memory_type_filter: MemoryTypeFilter::PREFER_HOST
+                    | MemoryTypeFilter::HOST_RANDOM_ACCESS,
+                ..Default::default()
+            },
+            extent[0] as u64 * extent[1] as u64 * 4,
+        )
+        .unwrap();
+
+        Self::with_target(
+            Target::Offscreen {
+                image: image.clone(),
+                download,
+            },
+            &[image],
+            memory_allocator,
+            queue,
+            texture_image,
+        )
+    }
+
+    // The last frame rendered by an offscreen interface.
+    #[cfg(test)]
+    pub fn read_image(&self) -> image::RgbaImage {
+        let Target::Offscreen { image, download } = &self.target else {
+            panic!("only offscreen interfaces can read back their image");
+        };
+
+        let [width, height, _] = image.extent();
+        image::RgbaImage::from_raw(width, height, download.read().unwrap().to_vec()).unwrap()
+    }
+
+    fn with_target(
+        target: Target,
+        images: &[Arc<Image>],
+        memory_allocator: Arc<StandardMemoryAllocator>,
+        queue: Arc<Queue>,
+        texture_image: image::DynamicImage,
+    ) -> Self {
+        let device = memory_allocator.device().clone();
+
         let descriptor_set_allocator =
             StandardDescriptorSetAllocator::new(device.clone(), Default::default());
         let command_buffer_allocator =
@@ -213,7 +466,7 @@ impl GraphicsInterface for VulkanGraphicsInterface {
             device.clone(),
             attachments: {
                 color: {
-                    format: swapchain.image_format(),
+                    format: images[0].format(),
                     samples: 1,
                     load_op: Clear,
                     store_op: Store
@@ -241,67 +494,19 @@ impl GraphicsInterface for VulkanGraphicsInterface {
             .entry_point("main")
             .unwrap();
 
-        let mut uploads = AutoCommandBufferBuilder::primary(
+        let texture = upload_texture(
+            memory_allocator.clone(),
             &command_buffer_allocator,
-            queue.queue_family_index(),
-            CommandBufferUsage::OneTimeSubmit,
-        )
-        .unwrap();
-
-        let texture = {
-            let extent = [texture_image.width(), texture_image.height(), 1];
-
-            let upload_buffer = Buffer::from_iter(
-                memory_allocator.clone(),
-                BufferCreateInfo {
-                    usage: BufferUsage::TRANSFER_SRC,
-                    ..Default::default()
-                },
-                AllocationCreateInfo {
-                    memory_type_filter: MemoryTypeFilter::PREFER_HOST
-                        | MemoryTypeFilter::HOST_SEQUENTIAL_WRITE,
-                    ..Default::default()
-                },
-                texture_image.into_rgba8().into_vec(),
-            )
-            .unwrap();
-
-            let image = Image::new(
-                memory_allocator.clone(),
-                ImageCreateInfo {
-                    image_type: ImageType::Dim2d,
-                    format: Format::R8G8B8A8_SRGB,
-                    extent,
-                    usage: ImageUsage::TRANSFER_DST | ImageUsage::SAMPLED,
-                    ..Default::default()
-                },
-                AllocationCreateInfo::default(),
-            )
-            .unwrap();
-
-            uploads
-                .copy_buffer_to_image(CopyBufferToImageInfo::buffer_image(
-                    upload_buffer,
-                    image.clone(),
-                ))
-                .unwrap();
-
-            ImageView::new_default(image).unwrap()
-        };
-
-        uploads
-            .build()
-            .unwrap()
-            .execute(queue.clone())
-            .unwrap()
-            .boxed();
+            queue.clone(),
+            texture_image,
+        );
 
         let sampler = Sampler::new(
             device.clone(),
             SamplerCreateInfo {
                 mag_filter: Filter::Linear,
                 min_filter: Filter::Linear,
-                address_mode: [SamplerAddressMode::Repeat; 3],
+                address_mode: [SamplerAddressMode::ClampToEdge; 3],
                 ..Default::default()
             },
         )
@@ -311,18 +516,17 @@ impl GraphicsInterface for VulkanGraphicsInterface {
             memory_allocator.clone(),
             vs.clone(),
             fs.clone(),
-            &images,
+            images,
             render_pass.clone(),
         );
 
         Self {
-            window,
+            target,
             device,
             framebuffers,
             pipeline,
             queue,
             render_pass,
-            swapchain,
             uniform_buffer,
             memory_allocator,
             descriptor_set_allocator,
@@ -333,20 +537,12 @@ impl GraphicsInterface for VulkanGraphicsInterface {
             texture,
             recreate_swapchain: false,
             renderables: vec![],
+            retired: vec![],
         }
     }
 
-    fn add_renderable<V: super::Vertex>(
-        &mut self,
-        renderable: impl super::Renderable<V> + Send,
-    ) -> usize {
-        let index = match self.renderables.iter().position(|x| x.is_none()) {
-            Some(idx) => idx,
-            None => {
-                self.renderables.push(None);
-                self.renderables.len() - 1
-            }
-        };
+    fn create_renderable(&self, vertices: Vec<Vertex>, indices: Vec<u32>) -> VulkanRenderable {
+        let index_count = indices.len() as u32;
 
         let vertex_buffer = Buffer::from_iter(
             self.memory_allocator.clone(),
@@ -359,15 +555,7 @@ impl GraphicsInterface for VulkanGraphicsInterface {
                     | MemoryTypeFilter::HOST_SEQUENTIAL_WRITE,
                 ..Default::default()
             },
-            renderable.get_vertices().iter().map(|v| {
-                let point = v.get_point();
-                let tex_coords = v.get_tex_coords();
-
-                Vertex {
-                    position: [point.x, point.y, point.z],
-                    in_tex_coords: [tex_coords.x, tex_coords.y],
-                }
-            }),
+            vertices,
         )
         .unwrap();
         let index_buffer = Buffer::from_iter(
@@ -381,60 +569,42 @@ impl GraphicsInterface for VulkanGraphicsInterface {
                     | MemoryTypeFilter::HOST_SEQUENTIAL_WRITE,
                 ..Default::default()
             },
-            renderable.get_indices(),
+            indices,
         )
         .unwrap();
 
-        let vulkan_renderable = VulkanRenderable {
+        VulkanRenderable {
             vertex_buffer,
             index_buffer,
-        };
-
-        self.renderables[index] = Some(vulkan_renderable);
-
-        index
-    }
-
-    fn rm_renderable(&mut self, _id: usize) {
-        todo!()
-    }
-
-    fn render(&mut self, camera: super::Camera) {
-        let image_extent: [u32; 2] = self.window.inner_size().into();
-
-        if image_extent.contains(&0) {
-            return;
+            index_count,
+            transform: Transform::default(),
         }
+    }
 
-        if self.recreate_swapchain {
-            let (new_swapchain, new_images) = self
-                .swapchain
-                .recreate(SwapchainCreateInfo {
-                    image_extent,
-                    ..self.swapchain.create_info()
-                })
-                .expect("failed to recreate swapchain");
-
-            self.swapchain = new_swapchain;
-            let (new_pipeline, new_framebuffers) = create_pipeline_and_framebuffers(
-                self.memory_allocator.clone(),
-                self.vs.clone(),
-                self.fs.clone(),
-                &new_images,
-                self.render_pass.clone(),
-            );
-            self.pipeline = new_pipeline;
-            self.framebuffers = new_framebuffers;
-            self.recreate_swapchain = false;
+    fn extent(&self) -> [u32; 2] {
+        match &self.target {
+            Target::Window { swapchain, .. } => swapchain.image_extent(),
+            #[cfg(test)]
+            Target::Offscreen { image, .. } => [image.extent()[0], image.extent()[1]],
         }
+    }
 
+    // Records drawing every renderable into `framebuffer`, and copying the
+    // result into the download buffer when rendering offscreen.
+    fn record_frame(
+        &self,
+        camera: super::Camera,
+        framebuffer: usize,
+    ) -> Arc<PrimaryAutoCommandBuffer> {
         let uniform_buffer_subbuffer = {
+            // Where the camera is turned relative to looking at `center`,
+            // about its up axis and then its right axis.
             let rotation_x = Matrix3::from_angle_x(Rad(camera.theta_x));
             let rotation_y = Matrix3::from_angle_y(Rad(camera.theta_y));
-            let rotation = rotation_x * rotation_y;
+            let rotation = rotation_y * rotation_x;
 
-            let aspect =
-                self.swapchain.image_extent()[0] as f32 / self.swapchain.image_extent()[1] as f32;
+            let [width, height] = self.extent();
+            let aspect = width as f32 / height as f32;
             let proj = cgmath::perspective(
                 Rad(camera.fov),
                 aspect,
@@ -444,9 +614,11 @@ impl GraphicsInterface for VulkanGraphicsInterface {
             let view = Matrix4::look_at_rh(camera.eye, camera.center, camera.up);
             let scale = Matrix4::from_scale(camera.scale);
 
+            // Turning the camera turns what it sees the other way around it,
+            // so the inverse rotation is applied in view space. Rotations
+            // are orthogonal, so the inverse is the transpose.
             let uniform_data = vertex_shader::Data {
-                world: Matrix4::from(rotation).into(),
-                view: (view * scale).into(),
+                view: (Matrix4::from(rotation.transpose()) * view * scale).into(),
                 proj: proj.into(),
             };
 
@@ -458,7 +630,12 @@ impl GraphicsInterface for VulkanGraphicsInterface {
 
         let uniform_set = PersistentDescriptorSet::new(
             &self.descriptor_set_allocator,
-            self.pipeline.layout().set_layouts().get(0).unwrap().clone(),
+            self.pipeline
+                .layout()
+                .set_layouts()
+                .first()
+                .unwrap()
+                .clone(),
             [WriteDescriptorSet::buffer(0, uniform_buffer_subbuffer)],
             [],
         )
@@ -474,20 +651,6 @@ impl GraphicsInterface for VulkanGraphicsInterface {
         )
         .unwrap();
 
-        let (image_index, suboptimal, acquire_feature) =
-            match acquire_next_image(self.swapchain.clone(), None).map_err(Validated::unwrap) {
-                Ok(r) => r,
-                Err(VulkanError::OutOfDate) => {
-                    self.recreate_swapchain = true;
-                    return;
-                }
-                Err(e) => panic!("failed to acquire next image: {e}"),
-            };
-
-        if suboptimal {
-            self.recreate_swapchain = true;
-        }
-
         let mut builder = AutoCommandBufferBuilder::primary(
             &self.command_buffer_allocator,
             self.queue.queue_family_index(),
@@ -499,9 +662,7 @@ impl GraphicsInterface for VulkanGraphicsInterface {
             .begin_render_pass(
                 RenderPassBeginInfo {
                     clear_values: vec![Some([0.0, 0.0, 1.0, 1.0].into()), Some(1f32.into())],
-                    ..RenderPassBeginInfo::framebuffer(
-                        self.framebuffers[image_index as usize].clone(),
-                    )
+                    ..RenderPassBeginInfo::framebuffer(self.framebuffers[framebuffer].clone())
                 },
                 Default::default(),
             )
@@ -517,17 +678,20 @@ impl GraphicsInterface for VulkanGraphicsInterface {
             .unwrap();
 
         for renderable in &self.renderables {
-            match renderable {
-                Some(renderable) => {
-                    command_buffer_builder = command_buffer_builder
-                        .bind_vertex_buffers(0, renderable.vertex_buffer.clone())
-                        .unwrap()
-                        .bind_index_buffer(renderable.index_buffer.clone())
-                        .unwrap()
-                        .draw_indexed(renderable.index_buffer.len() as u32, 1, 0, 0, 0)
-                        .unwrap();
-                }
-                _ => {}
+            if let Slot::Used(renderable) = renderable {
+                let model = vertex_shader::Model {
+                    model: renderable.transform.matrix().into(),
+                };
+
+                command_buffer_builder = command_buffer_builder
+                    .push_constants(self.pipeline.layout().clone(), 0, model)
+                    .unwrap()
+                    .bind_vertex_buffers(0, renderable.vertex_buffer.clone())
+                    .unwrap()
+                    .bind_index_buffer(renderable.index_buffer.clone())
+                    .unwrap()
+                    .draw_indexed(renderable.index_count, 1, 0, 0, 0)
+                    .unwrap();
             }
         }
 
@@ -535,34 +699,165 @@ impl GraphicsInterface for VulkanGraphicsInterface {
             .end_render_pass(Default::default())
             .unwrap();
 
-        let command_buffer = builder.build().unwrap();
+        #[cfg(test)]
+        if let Target::Offscreen { image, download } = &self.target {
+            builder
+                .copy_image_to_buffer(
+                    vulkano::command_buffer::CopyImageToBufferInfo::image_buffer(
+                        image.clone(),
+                        download.clone(),
+                    ),
+                )
+                .unwrap();
+        }
 
-        let execution = sync::now(self.device.clone())
-            .join(acquire_feature)
-            .then_execute(self.queue.clone(), command_buffer)
-            .unwrap()
-            .then_swapchain_present(
-                self.queue.clone(),
-                SwapchainPresentInfo::swapchain_image_index(self.swapchain.clone(), image_index),
-            )
-            .then_signal_fence_and_flush();
+        builder.build().unwrap()
+    }
+}
 
-        match execution.map_err(Validated::unwrap) {
-            Ok(future) => {
-                future.wait(None).unwrap();
-            }
-            Err(VulkanError::OutOfDate) => {
-                self.recreate_swapchain = true;
-            }
-            Err(e) => {
-                println!("failed to flush future: {e}");
+fn vertices<V: super::Vertex>(renderable: &impl super::Renderable<V>) -> Vec<Vertex> {
+    renderable
+        .get_vertices()
+        .iter()
+        .map(|v| {
+            let point = v.get_point();
+            let tex_coords = v.get_tex_coords();
+
+            Vertex {
+                position: [point.x, point.y, point.z],
+                in_tex_coords: [tex_coords.x, tex_coords.y],
             }
-        }
-    }
+        })
+        .collect()
+}
 
-    fn on_resized(&mut self, _new_size: winit::dpi::PhysicalSize<u32>) {
-        self.recreate_swapchain = true;
-    }
+fn create_instance(enabled_extensions: InstanceExtensions) -> Arc<Instance> {
+    let library = VulkanLibrary::new().unwrap();
+
+    Instance::new(
+        library,
+        InstanceCreateInfo {
+            flags: InstanceCreateFlags::ENUMERATE_PORTABILITY,
+            enabled_extensions,
+            ..Default::default()
+        },
+    )
+    .unwrap()
+}
+
+// Picks the best device with a graphics queue, that can also present to
+// `surface` if there is one.
+fn create_device(instance: Arc<Instance>, surface: Option<&Surface>) -> (Arc<Device>, Arc<Queue>) {
+    let device_extensions = DeviceExtensions {
+        khr_swapchain: surface.is_some(),
+        ..DeviceExtensions::empty()
+    };
+
+    let (physical_device, queue_family_index) = instance
+        .enumerate_physical_devices()
+        .unwrap()
+        .filter(|p| p.supported_extensions().contains(&device_extensions))
+        .filter_map(|p| {
+            p.queue_family_properties()
+                .iter()
+                .enumerate()
+                .position(|(i, q)| {
+                    q.queue_flags.intersects(QueueFlags::GRAPHICS)
+                        && surface.is_none_or(|surface| {
+                            p.surface_support(i as u32, surface).unwrap_or(false)
+                        })
+                })
+                .map(|i| (p, i as u32))
+        })
+        .min_by_key(|(p, _)| match p.properties().device_type {
+            PhysicalDeviceType::DiscreteGpu => 0,
+            PhysicalDeviceType::IntegratedGpu => 1,
+            PhysicalDeviceType::VirtualGpu => 2,
+            PhysicalDeviceType::Cpu => 3,
+            PhysicalDeviceType::Other => 4,
+            _ => 5,
+        })
+        .expect("no suitable device found");
+
+    let (device, mut queues) = Device::new(
+        physical_device,
+        DeviceCreateInfo {
+            enabled_extensions: device_extensions,
+            queue_create_infos: vec![QueueCreateInfo {
+                queue_family_index,
+                ..Default::default()
+            }],
+            ..Default::default()
+        },
+    )
+    .unwrap();
+
+    (device, queues.next().unwrap())
+}
+
+// Copies `texture_image` into a new sampled image on the GPU.
+fn upload_texture(
+    memory_allocator: Arc<StandardMemoryAllocator>,
+    command_buffer_allocator: &StandardCommandBufferAllocator,
+    queue: Arc<Queue>,
+    texture_image: image::DynamicImage,
+) -> Arc<ImageView> {
+    let mut uploads = AutoCommandBufferBuilder::primary(
+        command_buffer_allocator,
+        queue.queue_family_index(),
+        CommandBufferUsage::OneTimeSubmit,
+    )
+    .unwrap();
+
+    let texture = {
+        let extent = [texture_image.width(), texture_image.height(), 1];
+
+        let upload_buffer = Buffer::from_iter(
+            memory_allocator.clone(),
+            BufferCreateInfo {
+                usage: BufferUsage::TRANSFER_SRC,
+                ..Default::default()
+            },
+            AllocationCreateInfo {
+                memory_type_filter: MemoryTypeFilter::PREFER_HOST
+                    | MemoryTypeFilter::HOST_SEQUENTIAL_WRITE,
+                ..Default::default()
+            },
+            texture_image.into_rgba8().into_vec(),
+        )
+        .unwrap();
+
+        let image = Image::new(
+            memory_allocator.clone(),
+            ImageCreateInfo {
+                image_type: ImageType::Dim2d,
+                format: Format::R8G8B8A8_SRGB,
+                extent,
+                usage: ImageUsage::TRANSFER_DST | ImageUsage::SAMPLED,
+                ..Default::default()
+            },
+            AllocationCreateInfo::default(),
+        )
+        .unwrap();
+
+        uploads
+            .copy_buffer_to_image(CopyBufferToImageInfo::buffer_image(
+                upload_buffer,
+                image.clone(),
+            ))
+            .unwrap();
+
+        ImageView::new_default(image).unwrap()
+    };
+
+    uploads
+        .build()
+        .unwrap()
+        .execute(queue.clone())
+        .unwrap()
+        .boxed();
+
+    texture
 }
 
 fn create_pipeline_and_framebuffers(
@@ -676,14 +971,18 @@ mod vertex_shader {
             layout(location = 0) out vec2 tex_coords;
 
             layout(set = 0, binding = 0) uniform Data {
-                mat4 world;
                 mat4 view;
                 mat4 proj;
             } uniforms;
 
+            // The transform of the renderable being drawn.
+            layout(push_constant) uniform Model {
+                mat4 model;
+            } push;
+
             void main() {
-                mat4 worldview = uniforms.view * uniforms.world;
-                gl_Position = uniforms.proj * worldview * vec4(position, 1.0);
+                mat4 modelview = uniforms.view * push.model;
+                gl_Position = uniforms.proj * modelview * vec4(position, 1.0);
                 tex_coords = in_tex_coords;
             }
         ",
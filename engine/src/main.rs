@@ -1,9 +1,11 @@
 mod game;
 mod graphics;
 
+use cgmath::Rotation3;
 use clap::Parser;
+use std::io;
 use std::path::PathBuf;
-use std::sync::Arc;
+use std::sync::{mpsc, Arc};
 use std::time;
 use winit::{
     event::{Event, WindowEvent},
@@ -12,10 +14,16 @@ use winit::{
 };
 
 use crate::{
-    game::{Game, Profile},
-    graphics::{vulkan::VulkanGraphicsInterface, GraphicsInterface, Renderable, Vertex},
+    game::{Game, Module, ModuleWatcher, Profile, ProfileError},
+    graphics::{
+        atlas::TextureAtlas, null::NullGraphicsInterface, vulkan::VulkanGraphicsInterface,
+        GraphicsInterface, Renderable, Transform, Vertex,
+    },
 };
 
+// How often headless runs tick.
+const FRAME_TIME: time::Duration = time::Duration::from_millis(16);
+
 #[derive(Clone)]
 struct StaticVertex {
     point: cgmath::Point3<f32>,
@@ -46,37 +54,161 @@ impl Renderable<StaticVertex> for Triangle {
     }
 }
 
-fn main_graphics() {
-    let event_loop = EventLoop::new();
-    let window = WindowBuilder::new().build(&event_loop).unwrap();
-
-    let mut graphics_interface = VulkanGraphicsInterface::new(
-        event_loop,
-        window,
-        image::open("texture.png").unwrap(),
-    );
-
-    let graphics_loop = GraphicsLoop::new(graphics_interface);
-
-    graphics_interface.add_renderable(Triangle {
+fn triangle(atlas: &TextureAtlas) -> Triangle {
+    Triangle {
         vertices: [
             StaticVertex {
-                point: cgmath::point3(0.0, 1.0, 2.0),
-                tex_coords: cgmath::point2(0.5, 0.0),
+                point: cgmath::point3(0.0, 1.0, 0.0),
+                tex_coords: atlas.uv("core:triangle", cgmath::point2(0.5, 0.0)),
             },
             StaticVertex {
-                point: cgmath::point3(-1.0, -1.0, 2.0),
-                tex_coords: cgmath::point2(0.0, 1.0),
+                point: cgmath::point3(-1.0, -1.0, 0.0),
+                tex_coords: atlas.uv("core:triangle", cgmath::point2(0.0, 1.0)),
             },
             StaticVertex {
-                point: cgmath::point3(1.0, -1.0, 2.0),
-                tex_coords: cgmath::point2(1.0, 1.0),
+                point: cgmath::point3(1.0, -1.0, 0.0),
+                tex_coords: atlas.uv("core:triangle", cgmath::point2(1.0, 1.0)),
             },
         ],
+    }
+}
+
+fn build_atlas(game: &Game) -> Result<TextureAtlas, String> {
+    game.load_textures()
+        .map_err(|e| e.to_string())
+        .and_then(|textures| TextureAtlas::build(textures).map_err(|e| e.to_string()))
+}
+
+// Reads lines from stdin on a thread of its own, so frames don't wait for
+// them.
+fn console() -> mpsc::Receiver<String> {
+    let (sender, receiver) = mpsc::channel();
+
+    std::thread::spawn(move || {
+        for line in io::stdin().lines() {
+            let Ok(line) = line else { break };
+            if sender.send(line).is_err() {
+                break;
+            }
+        }
     });
 
-    let mut last_render = time::Instant::now();
-    let mut rotation = 0f32;
+    receiver
+}
+
+// What every frame updates, whether or not anything is drawn.
+struct Frames<G: GraphicsInterface> {
+    game: Game,
+    watcher: Option<ModuleWatcher>,
+    // Commands typed into the console, one per line.
+    console: mpsc::Receiver<String>,
+    graphics: G,
+    triangle_id: usize,
+    last_render: time::Instant,
+    rotation: f32,
+}
+
+impl<G: GraphicsInterface> Frames<G> {
+    fn new(
+        game: Game,
+        watcher: Option<ModuleWatcher>,
+        mut graphics: G,
+        atlas: &TextureAtlas,
+    ) -> Self {
+        let triangle_id = graphics.add_renderable(triangle(atlas));
+
+        Self {
+            game,
+            watcher,
+            console: console(),
+            graphics,
+            triangle_id,
+            last_render: time::Instant::now(),
+            rotation: 0.0,
+        }
+    }
+
+    fn frame(&mut self) {
+        let edited = self
+            .watcher
+            .as_mut()
+            .map(ModuleWatcher::poll)
+            .unwrap_or_default();
+        if !edited.is_empty() {
+            // Texture coordinates point into the old atlas, so the
+            // renderables are rebuilt along with it.
+            let reloaded = self
+                .game
+                .reload(&edited)
+                .map_err(|e| e.to_string())
+                .and_then(|()| build_atlas(&self.game));
+
+            match reloaded {
+                Ok(atlas) => {
+                    self.graphics
+                        .set_texture(image::DynamicImage::ImageRgba8(atlas.image().clone()));
+                    self.graphics
+                        .update_renderable(self.triangle_id, triangle(&atlas));
+
+                    // The reloaded profile may resolve to other modules.
+                    self.watcher = Some(ModuleWatcher::new(
+                        self.game.modules().iter().map(Module::source),
+                    ));
+                    println!("reloaded modules");
+                }
+                Err(e) => eprintln!("failed to reload: {e}"),
+            }
+        }
+
+        // The first word names the command, the rest are its arguments.
+        while let Ok(line) = self.console.try_recv() {
+            let mut words = line.split_whitespace();
+            let Some(name) = words.next() else {
+                continue;
+            };
+            if let Err(e) = self.game.run_command(name, &words.collect::<Vec<_>>()) {
+                eprintln!("{e}");
+            }
+        }
+
+        if let Err(e) = self.game.dispatch_event("tick", &[]) {
+            eprintln!("{e}");
+        }
+
+        self.rotation += 10f32 * self.last_render.elapsed().as_secs_f32();
+        self.last_render = time::Instant::now();
+        self.graphics.set_transform(
+            self.triangle_id,
+            Transform {
+                translation: cgmath::vec3(0.0, 0.0, 2.0),
+                rotation: cgmath::Quaternion::from_angle_y(cgmath::Deg(self.rotation)),
+                ..Default::default()
+            },
+        );
+        self.graphics.render(graphics::Camera {
+            theta_x: 0.0,
+            theta_y: 0.0,
+            fov: (70.0f32).to_radians(),
+            near_cutoff: 0.01,
+            far_cutoff: 100.0,
+            eye: cgmath::point3(0.0, 0.0, 0.0),
+            center: cgmath::point3(0.0, 0.0, 1.0),
+            up: cgmath::vec3(0.0, 1.0, 0.0),
+            scale: 1.0,
+        });
+    }
+}
+
+fn main_graphics(atlas: TextureAtlas, game: Game, watcher: Option<ModuleWatcher>) {
+    let event_loop = EventLoop::new();
+    let window = Arc::new(WindowBuilder::new().build(&event_loop).unwrap());
+
+    let graphics_interface = VulkanGraphicsInterface::new(
+        &event_loop,
+        window.clone(),
+        image::DynamicImage::ImageRgba8(atlas.image().clone()),
+    );
+    let mut frames = Frames::new(game, watcher, graphics_interface, &atlas);
 
     event_loop.run(move |event, _, control_flow| match event {
         Event::WindowEvent {
@@ -87,40 +219,218 @@ fn main_graphics() {
                 *control_flow = ControlFlow::Exit;
             }
             WindowEvent::Resized(new_size) => {
-                graphics_interface.on_resized(new_size);
+                frames.graphics.on_resized(new_size);
             }
             _ => {}
         },
-        Event::MainEventsCleared => {
-            rotation += 10f32 * last_render.elapsed().as_secs_f32();
-            last_render = time::Instant::now();
-            graphics_interface.render(graphics::Camera {
-                theta_x: 0.0,
-                theta_y: rotation.to_radians(),
-                fov: (70.0f32).to_radians(),
-                near_cutoff: 0.01,
-                far_cutoff: 100.0,
-                eye: cgmath::point3(0.0, 0.0, 0.0),
-                center: cgmath::point3(0.0, 0.0, 1.0),
-                up: cgmath::vec3(0.0, 1.0, 0.0),
-                scale: 1.0,
-            });
-        }
+        Event::MainEventsCleared => frames.frame(),
         _ => {}
     });
 }
 
+// Runs frames without a window or Vulkan, `count` of them or until killed.
+fn main_headless(
+    atlas: TextureAtlas,
+    game: Game,
+    watcher: Option<ModuleWatcher>,
+    count: Option<u64>,
+) {
+    let mut frames = Frames::new(game, watcher, NullGraphicsInterface::default(), &atlas);
+
+    while count.is_none_or(|count| frames.graphics.stats().frames < count) {
+        let started = time::Instant::now();
+        frames.frame();
+        std::thread::sleep(FRAME_TIME.saturating_sub(started.elapsed()));
+    }
+
+    let stats = frames.graphics.stats();
+    println!(
+        "ran {} frames, drawing {} renderables with {} vertices and {} indices",
+        stats.frames, stats.renderables, stats.vertices, stats.indices
+    );
+}
+
 #[derive(Parser, Debug)]
 #[command(author, version, about, long_about = None)]
 struct Args {
     profile_dir: PathBuf,
     modules_dir: PathBuf,
+
+    /// Reload modules when their files change
+    #[arg(long)]
+    watch: bool,
+
+    /// Accept modules whose version or content no longer match the lockfile and rewrite it
+    #[arg(long)]
+    update_lock: bool,
+
+    /// Print the settings of every module in the profile as Markdown and exit
+    #[arg(long)]
+    dump_settings: bool,
+
+    /// Load the profile, print its data tables with all patches applied and exit
+    #[arg(long)]
+    dump_data: bool,
+
+    /// Load the profile, print its modules in load order and exit
+    #[arg(long)]
+    list_modules: bool,
+
+    /// Load the profile, print the blocks, items and entities modules registered and exit
+    #[arg(long)]
+    dump_content: bool,
+
+    /// Locale to translate text to, e.g. `en-GB`
+    #[arg(long, default_value = "en")]
+    locale: String,
+
+    /// Load the profile, print the translation keys each locale is missing and exit
+    #[arg(long)]
+    lang_report: bool,
+
+    /// Run the game without a window or Vulkan driver
+    #[arg(long)]
+    headless: bool,
+
+    /// Exit after this many frames, only with `--headless`
+    #[arg(long, requires = "headless")]
+    frames: Option<u64>,
+}
+
+// Prints the error along with the lines of `profile.yml` around it.
+fn report_profile_error(e: &ProfileError) {
+    eprintln!("error: {e}");
+
+    if let (Some(source), Some((line, column))) = (&e.source, e.location) {
+        eprint!("{}", snippet(source, line, column));
+    }
+}
+
+// The lines of `source` around the 1-based `line`, with a caret under
+// `column`.
+fn snippet(source: &str, line: usize, column: usize) -> String {
+    use std::fmt::Write;
+
+    let lines = source.lines().collect::<Vec<_>>();
+    let first = line.saturating_sub(3);
+    let last = (line + 2).min(lines.len());
+    let width = last.to_string().len();
+
+    let mut out = String::new();
+    writeln!(out, "{:width$} |", "").unwrap();
+    for (idx, text) in lines.iter().enumerate().take(last).skip(first) {
+        writeln!(out, "{:width$} | {text}", idx + 1).unwrap();
+        if idx + 1 == line {
+            writeln!(out, "{:width$} | {:>column$}", "", "^").unwrap();
+        }
+    }
+    writeln!(out, "{:width$} |", "").unwrap();
+
+    out
 }
 
 fn main() {
     let args = Args::parse();
 
-    let profile = Profile::load(args.profile_dir);
+    let profile = match Profile::load(args.profile_dir) {
+        Ok(profile) => profile,
+        Err(e) => {
+            report_profile_error(&e);
+            std::process::exit(1);
+        }
+    };
+
+    let mut game = Game::new(profile, args.modules_dir, args.locale);
+
+    if args.dump_settings {
+        match game.dump_settings() {
+            Ok(dump) => print!("{dump}"),
+            Err(e) => {
+                eprintln!("{e}");
+                std::process::exit(1);
+            }
+        }
+        return;
+    }
+
+    if let Err(e) = game.load_modules(args.update_lock) {
+        eprintln!("{e}");
+        std::process::exit(1);
+    }
+
+    if args.dump_data {
+        print!("{}", game.dump_data());
+        return;
+    }
+
+    if args.list_modules {
+        print!("{}", game.list_modules());
+        return;
+    }
+
+    if args.dump_content {
+        print!("{}", game.dump_content());
+        return;
+    }
+
+    if args.lang_report {
+        print!("{}", game.lang_report());
+        return;
+    }
+
+    let atlas = match build_atlas(&game) {
+        Ok(atlas) => atlas,
+        Err(e) => {
+            eprintln!("{e}");
+            std::process::exit(1);
+        }
+    };
+
+    let watcher = args
+        .watch
+        .then(|| ModuleWatcher::new(game.modules().iter().map(Module::source)));
+
+    if args.headless {
+        main_headless(atlas, game, watcher, args.frames);
+    } else {
+        main_graphics(atlas, game, watcher);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-    let game = Game::new(profile, args.modules_dir);
+    #[test]
+    fn snippets_point_at_the_error() {
+        let source = "name: Test\nmodules:\n  - core\n  - co/re\n  - ui\n  - extra\n  - more\n";
+
+        assert_eq!(
+            snippet(source, 4, 5),
+            "  |\n\
+             2 | modules:\n\
+             3 |   - core\n\
+             4 |   - co/re\n\
+             \x20 |     ^\n\
+             5 |   - ui\n\
+             6 |   - extra\n\
+             \x20 |\n"
+        );
+
+        // Cut off at the start and end of the file.
+        assert_eq!(
+            snippet("name: [\n", 1, 7),
+            "  |\n1 | name: [\n  |       ^\n  |\n"
+        );
+    }
+
+    #[test]
+    fn snippets_widen_for_long_files() {
+        let source = (1..=12).map(|n| format!("line {n}\n")).collect::<String>();
+
+        assert_eq!(
+            snippet(&source, 10, 1),
+            "   |\n 8 | line 8\n 9 | line 9\n10 | line 10\n   | ^\n11 | line 11\n12 | line 12\n   |\n"
+        );
+    }
 }